@@ -0,0 +1,140 @@
+//!
+//! Register access over the configuration bus, separating the register
+//! sequences from the transport they are written over
+//!
+
+use crate::sequence::{register16_mask, register16_part, table_entries};
+
+/// Read and write access to the registers of the currently selected bank
+pub(crate) trait RegisterAccess {
+    type Error;
+
+    /// Read the value of a register
+    fn read(&mut self, register: u8) -> Result<u8, Self::Error>;
+
+    /// Write a value to a register
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Self::Error>;
+
+    /// Write a register table one entry at a time.  The entries can not share
+    /// an I2C transaction: an SCCB write cycle carries a single sub-address
+    /// and data byte (SCCB Functional Specification, 3-phase write
+    /// transmission cycle) with no sub-address auto-increment, and embedded-hal
+    /// sends adjacent writes of one transaction without a restart
+    fn write_many(&mut self, registers: &[[u8; 2]]) -> Result<(), Self::Error> {
+        for register in table_entries(registers) {
+            self.write(register[0], register[1])?;
+        }
+        Ok(())
+    }
+
+    /// Read a 16-bit value split across registers, `parts` giving each
+    /// register with the number of value bits held in its low bits, least
+    /// significant part first
+    fn read_register16(&mut self, parts: &[(u8, u8)]) -> Result<u16, Self::Error> {
+        let mut value = 0u16;
+        let mut shift = 0;
+        for &(register, bits) in parts {
+            let part = self.read(register)? & register16_mask(bits);
+            value |= (part as u16) << shift;
+            shift += bits;
+        }
+        Ok(value)
+    }
+
+    /// Write a 16-bit value split across registers as described by `parts`
+    /// (see `read_register16`), leaving the other bits of each register
+    /// untouched
+    fn write_register16(&mut self, parts: &[(u8, u8)], value: u16) -> Result<(), Self::Error> {
+        let mut value = value;
+        for &(register, bits) in parts {
+            let current = if bits == 8 { 0 } else { self.read(register)? };
+            self.write(register, register16_part(current, bits, value))?;
+            value = value.checked_shr(bits as u32).unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+/// Select a bank and replace the masked bits of one of its registers with
+/// those of `value`, leaving the other bits untouched
+pub(crate) fn modify_register<R: RegisterAccess>(
+    access: &mut R, bank: u8, register: u8, mask: u8, value: u8
+) -> Result<(), R::Error> {
+    access.write(0xFF, bank)?;
+    let current = access.read(register)?;
+    access.write(register, (current & !mask) | (value & mask))
+}
+
+/// Select a bank and set or clear the masked bits of one of its registers,
+/// leaving the other bits untouched
+pub(crate) fn update_bits<R: RegisterAccess>(
+    access: &mut R, bank: u8, register: u8, mask: u8, set: bool
+) -> Result<(), R::Error> {
+    modify_register(access, bank, register, mask, if set { mask } else { 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Register access serving reads from a map of register values (unset
+    /// registers read as 0) and recording each write as (register, value)
+    #[derive(Default)]
+    struct FakeRegisters {
+        registers: HashMap<u8, u8>,
+        writes: Vec<(u8, u8)>,
+    }
+
+    impl RegisterAccess for FakeRegisters {
+        type Error = core::convert::Infallible;
+
+        fn read(&mut self, register: u8) -> Result<u8, Self::Error> {
+            Ok(self.registers.get(&register).copied().unwrap_or(0))
+        }
+
+        fn write(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+            self.registers.insert(register, value);
+            self.writes.push((register, value));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn modify_register_replaces_only_the_masked_bits() {
+        let mut access = FakeRegisters::default();
+        access.registers.insert(0x12, 0b1010_1010);
+
+        modify_register(&mut access, 0x01, 0x12, 0x0F, 0b0101_0101).unwrap();
+        assert_eq!(access.writes, [(0xFF, 0x01), (0x12, 0b1010_0101)]);
+    }
+
+    #[test]
+    fn update_bits_sets_and_clears_the_masked_bits() {
+        let mut access = FakeRegisters::default();
+        access.registers.insert(0xDA, 0x10);
+
+        update_bits(&mut access, 0x00, 0xDA, 0x01, true).unwrap();
+        assert_eq!(access.registers[&0xDA], 0x11);
+        update_bits(&mut access, 0x00, 0xDA, 0x10, false).unwrap();
+        assert_eq!(access.registers[&0xDA], 0x01);
+        assert_eq!(access.writes, [(0xFF, 0x00), (0xDA, 0x11), (0xFF, 0x00), (0xDA, 0x01)]);
+    }
+
+    #[test]
+    fn register16_values_split_across_and_combine_from_the_parts() {
+        // REG04[1:0], AEC[7:0] and REG45[5:0] like the exposure
+        let parts = [(0x04, 2), (0x10, 8), (0x45, 6)];
+        let mut access = FakeRegisters::default();
+        access.registers.insert(0x04, 0xA8);
+        access.registers.insert(0x45, 0xC0);
+
+        access.write_register16(&parts, 0xABCD).unwrap();
+        // 0xABCD is 0b101010_11110011_01
+        assert_eq!(access.registers[&0x04], 0xA9);
+        assert_eq!(access.registers[&0x10], 0xF3);
+        assert_eq!(access.registers[&0x45], 0xEA);
+        assert_eq!(access.read_register16(&parts).unwrap(), 0xABCD);
+    }
+}
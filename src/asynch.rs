@@ -0,0 +1,1653 @@
+//!
+//! Async Driver for the OV2640 ArduCam Module using the embedded-hal-async traits
+//!
+
+use embedded_hal::{spi::Operation, digital::{OutputPin, Error as _}};
+use embedded_hal_async::{i2c::{I2c, SevenBitAddress}, spi::SpiDevice, delay::DelayNs};
+
+use crate::config::*;
+use crate::error::OV2640Error;
+use crate::register::{arduchip::*, dsp::*, sensor::*};
+use crate::sequence::*;
+use crate::readout::Frame;
+use crate::{readable_registers, NoPin, ModuleInfo, Status, I2C_ADDRESS, DSP_BANK, SENSOR_BANK, GPIO_PWDN_MASK, FIFO_CLEAR_MASK, FIFO_START_MASK, FIFO_RDPTR_RST_MASK, CAPTURE_COMPLETE_MASK, FIFO_BURST, FIFO_SIZE_READ_ATTEMPTS, JPEG_SCAN_CHUNK_SIZE, CAPTURE_POLL_INTERVAL_MS, RESET_DELAY_MS};
+
+/// Async counterpart of `OV2640` over the embedded-hal-async I2C and SPI traits,
+/// sharing its register sequences.  The power down and reset pins use the
+/// blocking embedded-hal `OutputPin`, as setting a pin never waits.
+///
+/// Only the `CaptureInProgress` handle (`start_capture` returns nothing here,
+/// poll `is_capture_done` instead), the `FrameReader` impl and the typestate
+/// wrapper are sync-only
+pub struct OV2640Async<I2C, SPI, PWDN = NoPin, RST = NoPin> {
+    // Configuration
+    configuration: Configuration,
+    // I2C Peripheral
+    i2c: Option<I2C>,
+    // SPI Peripheral
+    spi: Option<SPI>,
+    // Power Down Pin (when not controlled by the ArduChip)
+    pwdn: Option<PWDN>,
+    // Reset Pin
+    reset: Option<RST>,
+    // I2C Address
+    i2c_address: SevenBitAddress,
+    // State of the last capture
+    capture_state: CaptureState,
+    // Register bank last selected, None when unknown
+    current_bank: Option<u8>,
+    // Banding filter COM3 selects while auto banding is off
+    manual_banding_filter: BandingFilter,
+    // Largest FIFO read allowed, None for no limit beyond the FIFO size
+    max_read_bytes: Option<usize>,
+    // Milliseconds waited after a soft reset
+    reset_delay_ms: u32,
+    // Bytes per second of the last timed image read
+    last_read_throughput: Option<u32>,
+}
+
+impl<I2C, SPI, PWDN, RST> OV2640Async<I2C, SPI, PWDN, RST> {
+    fn from_parts(
+        configuration: Configuration,
+        i2c: Option<I2C>,
+        spi: Option<SPI>,
+        pwdn: Option<PWDN>,
+        reset: Option<RST>,
+    ) -> Self {
+        Self {
+            configuration,
+            i2c,
+            spi,
+            pwdn,
+            reset,
+            i2c_address: I2C_ADDRESS,
+            capture_state: CaptureState::Idle,
+            current_bank: None,
+            manual_banding_filter: manual_banding_filter(configuration.banding_filter),
+            max_read_bytes: None,
+            reset_delay_ms: RESET_DELAY_MS,
+            last_read_throughput: None,
+        }
+    }
+}
+
+impl<I2C, SPI, I2CErr, SPIErr> OV2640Async<I2C, SPI, NoPin, NoPin> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr> {
+    /// Initialize a new async OV2640 Driver
+    pub fn new(i2c: Option<I2C>, spi: Option<SPI>) -> Self {
+        Self::with_configuration(ConfigurationBuilder::default().build(), i2c, spi)
+    }
+
+    /// Initialize a new async OV2640 Driver, failing with `NoPeripherals` if
+    /// neither an I2C nor an SPI peripheral is given
+    pub fn try_new(
+        i2c: Option<I2C>, spi: Option<SPI>
+    ) -> Result<Self, OV2640Error<I2CErr, SPIErr>> {
+        if i2c.is_none() && spi.is_none() {
+            return Err(OV2640Error::NoPeripherals);
+        }
+        Ok(Self::new(i2c, spi))
+    }
+
+    /// Initialize a new async OV2640 Driver with given configuration
+    pub fn with_configuration(
+        configuration: Configuration, i2c: Option<I2C>, spi: Option<SPI>
+    ) -> Self {
+        Self::from_parts(configuration, i2c, spi, None, None)
+    }
+
+    /// Initialize a new async OV2640 Driver at a non default I2C address
+    pub fn new_with_address(
+        i2c: Option<I2C>, spi: Option<SPI>, i2c_address: SevenBitAddress
+    ) -> Self {
+        let mut ov2640 = Self::new(i2c, spi);
+        ov2640.i2c_address = i2c_address;
+        ov2640
+    }
+}
+
+impl<I2C, SPI, PWDN, I2CErr, SPIErr> OV2640Async<I2C, SPI, PWDN, NoPin> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin {
+    /// Initialize a new async OV2640 Driver whose sensor power down line is
+    /// wired to a GPIO pin instead of the ArduChip
+    pub fn new_with_power_pin(i2c: Option<I2C>, spi: Option<SPI>, pwdn: PWDN) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, Some(pwdn), None)
+    }
+}
+
+impl<I2C, SPI, RST, I2CErr, SPIErr> OV2640Async<I2C, SPI, NoPin, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    RST: OutputPin {
+    /// Initialize a new async OV2640 Driver whose sensor reset line is wired to
+    /// a GPIO pin
+    pub fn new_with_reset_pin(i2c: Option<I2C>, spi: Option<SPI>, reset: RST) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, None, Some(reset))
+    }
+}
+
+impl<I2C, SPI, PWDN, RST, I2CErr, SPIErr> OV2640Async<I2C, SPI, PWDN, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin,
+    RST: OutputPin {
+    /// Initialize a new async OV2640 Driver whose sensor power down and reset
+    /// lines are both wired to GPIO pins
+    pub fn new_with_pins(i2c: Option<I2C>, spi: Option<SPI>, pwdn: PWDN, reset: RST) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, Some(pwdn), Some(reset))
+    }
+
+    /// Check that I2C is correctly connected to the OV2640 Module
+    pub async fn i2c_connected(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(TEST_REGISTER, 0x52).await?;
+        let result = self.read_spi(TEST_REGISTER).await?;
+        Ok(result == 0x52)
+    }
+
+    /// Check that SPI is correctly connected to the OV2640 Module
+    pub async fn spi_connected(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        let (high, low) = self.product_id().await?;
+        // Check a valid chip ID was found
+        Ok(is_ov2640_product_id(high, low))
+    }
+
+    /// Probe both interfaces of the module for bring-up diagnostics, reporting
+    /// which responded and the product ID.  Unlike `i2c_connected` and
+    /// `spi_connected` an interface that fails is reported rather than
+    /// returning its bus error
+    pub async fn probe(&mut self) -> Result<ModuleInfo, OV2640Error<I2CErr, SPIErr>> {
+        let spi_ok = match self.write_spi(TEST_REGISTER, 0x52).await {
+            Ok(()) => self.read_spi(TEST_REGISTER).await.is_ok_and(|value| value == 0x52),
+            Err(_) => false,
+        };
+        let product_id = self.product_id().await.ok();
+        Ok(ModuleInfo {
+            i2c_ok: product_id.is_some_and(|(high, low)| is_ov2640_product_id(high, low)),
+            spi_ok,
+            chip_id: product_id.map(|(high, low)| u16::from_be_bytes([high, low])),
+        })
+    }
+
+    /// Read the raw (high, low) product ID of the sensor, 0x26 low with a high
+    /// byte of 0x41 or 0x42 depending on the silicon revision
+    pub async fn product_id(&mut self) -> Result<(u8, u8), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let high = self.read_register(CHIP_ID_HIGH).await?;
+        let low = self.read_register(CHIP_ID_LOW).await?;
+        Ok((high, low))
+    }
+
+    /// Read the average luminance of the last frame as measured by the auto
+    /// exposure engine (YAVG), a cheap estimate of the ambient light level
+    pub async fn average_luminance(&mut self) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        self.read_register(YAVG).await
+    }
+
+    /// Read a register from the given bank (DSP_BANK or SENSOR_BANK) of the
+    /// sensor.  The bank is left selected after the read, which does not affect
+    /// the driver as every setter selects its own bank first
+    pub async fn read_sensor_register(
+        &mut self, bank: u8, register: u8
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        if !readable_registers(bank).contains(&register) {
+            return Err(OV2640Error::RegisterNotReadable);
+        }
+
+        self.select_bank(bank).await?;
+        self.read_register(register).await
+    }
+
+    /// Read every readable register of the given bank (DSP_BANK or SENSOR_BANK)
+    /// into `out` for diagnostics, `out[i]` holding the value of register
+    /// `readable_registers(bank)[i]`.  Returns the number of registers read
+    pub async fn dump_registers(
+        &mut self, bank: u8, out: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let registers = readable_registers(bank);
+        if registers.is_empty() {
+            return Err(OV2640Error::RegisterNotReadable);
+        }
+        if out.len() < registers.len() {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        self.select_bank(bank).await?;
+        for (value, register) in out.iter_mut().zip(registers) {
+            *value = self.read_register(*register).await?;
+        }
+        Ok(registers.len())
+    }
+
+    /// Initialize the OV2640 Driver with its configuration, leaving the DSP
+    /// bank selected
+    pub async fn init(&mut self, delay: &mut impl DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.set_image_format(self.configuration.image_format, delay).await?;
+        self.set_resolution(self.configuration.resolution).await?;
+        self.set_light_mode(self.configuration.light_mode).await?;
+        self.set_saturation(self.configuration.saturation).await?;
+        self.set_brightness(self.configuration.brightness).await?;
+        self.set_contrast(self.configuration.contrast).await?;
+        self.set_special_effect(self.configuration.special_effect).await?;
+        self.set_strobe(self.configuration.strobe).await?;
+        if self.configuration.image_format == ImageFormat::JPEG {
+            self.set_jpeg_quality(self.configuration.jpeg_quality).await?;
+        }
+        self.set_flip(self.configuration.flip).await?;
+        // The image format tables already leave the 50Hz filter enabled
+        if self.configuration.banding_filter != BandingFilter::Hz50 {
+            self.set_banding_filter(self.configuration.banding_filter).await?;
+        }
+        self.set_sharpness(self.configuration.sharpness).await?;
+        // Auto white balance would undo a light mode preset
+        if let WhiteBalance::Manual { .. } = self.configuration.white_balance {
+            self.set_white_balance(self.configuration.white_balance).await?;
+        }
+        self.set_gain_ceiling(self.configuration.gain_ceiling).await?;
+        if let Some(clock_divider) = self.configuration.clock_divider {
+            self.set_clock_divider(clock_divider).await?;
+        }
+        self.select_bank(DSP_BANK).await
+    }
+
+    /// Initialize the OV2640 Driver, retrying up to `retries` more times when
+    /// an I2C error occurs (such as the NAK some modules give the first write
+    /// after power up).  Returns the last error once the retries run out
+    pub async fn init_with_retries(
+        &mut self, delay: &mut impl DelayNs, retries: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let mut result = self.init(delay).await;
+        for _ in 0..retries {
+            match result {
+                Err(OV2640Error::I2CError(_)) => result = self.init(delay).await,
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Set the configuration of the OV2640 Driver
+    pub async fn set_configuration(
+        &mut self, configuration: Configuration, delay: &mut impl DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.configuration = configuration;
+        self.init(delay).await
+    }
+
+    /// Read back the image format and output size registers and compare them
+    /// against the values `init` wrote, returning false if the sensor did not
+    /// latch them (or they were changed since, for example by `set_window`)
+    pub async fn verify_configuration(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK).await?;
+        for register in VERIFIED_DSP_REGISTERS {
+            if let Some(expected) = expected_dsp_register(&self.configuration, register) {
+                if self.read_register(register).await? != expected {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Set the image format for the OV2640 Module
+    pub async fn set_image_format(
+        &mut self, image_format: ImageFormat, delay: &mut impl DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let has_resolution = image_format != ImageFormat::QVGA;
+        if has_resolution {
+            check_resolution(image_format, self.configuration.resolution)?;
+        }
+
+        self.write_image_format(image_format, delay).await?;
+        if has_resolution {
+            self.write_registers(resolution_registers(self.configuration.resolution)).await?;
+        }
+        self.configuration.image_format = image_format;
+        Ok(())
+    }
+
+    /// Set the resolution of the OV2640 Module
+    pub async fn set_resolution(
+        &mut self, resolution: Resolution
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        check_resolution(self.configuration.image_format, resolution)?;
+
+        self.write_registers(resolution_registers(resolution)).await?;
+        self.configuration.resolution = resolution;
+        Ok(())
+    }
+
+    /// Set the image format and resolution of the OV2640 Module together with
+    /// a single reset
+    pub async fn set_format_and_resolution(
+        &mut self, image_format: ImageFormat, resolution: Resolution, delay: &mut impl DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        check_resolution(image_format, resolution)?;
+
+        self.write_image_format(image_format, delay).await?;
+        self.write_registers(resolution_registers(resolution)).await?;
+        self.configuration.image_format = image_format;
+        self.configuration.resolution = resolution;
+        Ok(())
+    }
+
+    /// Configure the OV2640 Module to output RGB565 frames for a display.  The
+    /// resolution with the aspect ratio closest to the display's is chosen and
+    /// the DSP output is scaled down, keeping that aspect ratio, to fit within
+    /// the display.  Returns the resolution chosen and the size of the frames.
+    ///
+    /// The configuration keeps the resolution chosen, so `verify_configuration`
+    /// reports the output size as changed when the frames are scaled
+    pub async fn set_for_display(
+        &mut self, display_width: u16, display_height: u16, delay: &mut impl DelayNs
+    ) -> Result<(Resolution, (u16, u16)), OV2640Error<I2CErr, SPIErr>> {
+        let (resolution, size) = display_resolution(display_width, display_height)
+            .ok_or(OV2640Error::NoResolutionFitsDisplay)?;
+
+        self.set_format_and_resolution(ImageFormat::RGB565, resolution, delay).await?;
+        if size != resolution.dimensions() {
+            self.write_registers(&output_size_registers(size.0, size.1)).await?;
+        }
+        Ok((resolution, size))
+    }
+
+    /// Set the light mode of the OV2640 Module
+    pub async fn set_light_mode(
+        &mut self, light_mode: LightMode,
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(light_mode_registers(light_mode)).await?;
+        self.configuration.light_mode = light_mode;
+        Ok(())
+    }
+
+    /// Set the white balance of the OV2640 Module, either automatic or with
+    /// manual red, green and blue gains.  This overrides the light mode
+    pub async fn set_white_balance(
+        &mut self, white_balance: WhiteBalance
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        match white_balance {
+            WhiteBalance::Auto => self.write_registers(light_mode_registers(LightMode::Auto)).await?,
+            WhiteBalance::Manual { r_gain, g_gain, b_gain } => {
+                self.write_registers(&manual_white_balance_registers(r_gain, g_gain, b_gain)).await?
+            },
+        }
+        self.configuration.white_balance = white_balance;
+        Ok(())
+    }
+
+    /// Set the raw red, green and blue white balance gains (0xCC, 0xCD and
+    /// 0xCE of the DSP bank), turning off auto white balance
+    pub async fn set_white_balance_raw(
+        &mut self, regs: [u8; 3]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.set_white_balance(WhiteBalance::Manual { r_gain: regs[0], g_gain: regs[1], b_gain: regs[2] }).await
+    }
+
+    /// Set the saturation of the OV2640 Module
+    pub async fn set_saturation(
+        &mut self, saturation: Saturation
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&saturation_registers(saturation)).await?;
+        self.configuration.saturation = saturation;
+        Ok(())
+    }
+
+    /// Set the brightness of the OV2640 Module
+    pub async fn set_brightness(
+        &mut self, brightness: Brightness
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&brightness_registers(brightness)).await?;
+        self.configuration.brightness = brightness;
+        Ok(())
+    }
+
+    /// Set the contrast of the OV2640 Module
+    pub async fn set_contrast(
+        &mut self, contrast: Contrast
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&contrast_registers(contrast)).await?;
+        self.configuration.contrast = contrast;
+        Ok(())
+    }
+
+    /// Set the saturation as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Saturation2` and each `Saturation` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub async fn set_saturation_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&saturation_level_registers(check_level(level)?)).await
+    }
+
+    /// Set the brightness as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Brightness2` and each `Brightness` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub async fn set_brightness_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&brightness_level_registers(check_level(level)?)).await
+    }
+
+    /// Set the contrast as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Contrast2` and each `Contrast` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub async fn set_contrast_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&contrast_level_registers(check_level(level)?)).await
+    }
+
+    /// Set a custom gamma curve of the OV2640 Module, see `DEFAULT_GAMMA_CURVE`
+    /// and `LOW_LIGHT_GAMMA_CURVE` for presets
+    pub async fn set_gamma_curve(
+        &mut self, points: &[u8; GAMMA_CURVE_POINTS]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&gamma_curve_registers(points)).await
+    }
+
+    /// Set the special effect used by the OV2640 Module
+    pub async fn set_special_effect(
+        &mut self, special_effect: SpecialEffect
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&special_effect_registers(special_effect)).await?;
+        self.configuration.special_effect = special_effect;
+        Ok(())
+    }
+
+    /// Tint the image with fixed U and V chroma values, the effect behind the
+    /// tint presets of `SpecialEffect`: Antique / Sepia is (0x40, 0xA6), Bluish
+    /// (0xA0, 0x40), Greenish (0x40, 0x40), Reddish (0x40, 0xC0) and BlackWhite
+    /// (0x80, 0x80).  The tint is not recorded in the configuration
+    pub async fn set_special_effect_raw(
+        &mut self, u: u8, v: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&special_effect_uv_registers(0x18, u, v)).await
+    }
+
+    /// Set the sharpness (edge enhancement) of the OV2640 Module, failing with
+    /// `LevelOutOfRange` for a manual level above `MAX_SHARPNESS`
+    pub async fn set_sharpness(
+        &mut self, sharpness: Sharpness
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&sharpness_registers(sharpness)?).await?;
+        self.configuration.sharpness = sharpness;
+        Ok(())
+    }
+
+    /// Swap the byte order of each RGB565 or YUV422 pixel in the DSP, for
+    /// displays expecting the low byte first
+    pub async fn set_byte_swap(
+        &mut self, swap: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK).await?;
+        let image_mode = self.read_register(IMAGE_MODE).await?;
+        if swap {
+            self.write_register(IMAGE_MODE, image_mode | IMAGE_MODE_BYTE_SWAP).await?;
+        } else {
+            self.write_register(IMAGE_MODE, image_mode & !IMAGE_MODE_BYTE_SWAP).await?;
+        }
+        self.configuration.byte_swap = swap;
+        Ok(())
+    }
+
+    /// Turn the lens shading correction (LENC) of the DSP on or off.  Lens
+    /// correction brightens the corners of the image to reduce the vignetting
+    /// of the wide angle lenses fitted to most modules
+    pub async fn set_lens_correction(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK).await?;
+        let ctrl1 = self.read_register(CTRL1).await?;
+        if on {
+            self.write_register(CTRL1, ctrl1 | CTRL1_LENC).await?;
+        } else {
+            self.write_register(CTRL1, ctrl1 & !CTRL1_LENC).await?;
+        }
+        self.configuration.lens_correction = on;
+        Ok(())
+    }
+
+    /// Bypass the DSP to read the raw Bayer output of the sensor, for debugging
+    /// the sensor independently of the image pipeline.  While bypassed none of
+    /// the image format, resolution or image settings apply to the readout
+    pub async fn set_dsp_bypass(
+        &mut self, bypass: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK).await?;
+        let r_bypass = self.read_register(R_BYPASS).await?;
+        if bypass {
+            self.write_register(R_BYPASS, r_bypass | R_BYPASS_DSP).await
+        } else {
+            self.write_register(R_BYPASS, r_bypass & !R_BYPASS_DSP).await
+        }
+    }
+
+    /// Set the polarities of the parallel (DVP) output through COM10, to match
+    /// a DCMI host: whether data changes on the rising edge of PCLK and whether
+    /// HREF and VSYNC are active high.  The ArduChip FIFO does not depend on
+    /// these settings
+    pub async fn set_sync_polarity(
+        &mut self, pclk_rising: bool, href_active_high: bool, vsync_active_high: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com10 = self.read_register(COM10).await?;
+        self.write_register(
+            COM10, sync_polarity_com10(com10, pclk_rising, href_active_high, vsync_active_high)
+        ).await
+    }
+
+    /// Set the JPEG quality of the OV2640 Module.  The encoder only produces
+    /// baseline JPEG, there is no register selecting progressive output
+    pub async fn set_jpeg_quality(
+        &mut self, jpeg_quality: JpegQuality
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if self.configuration.image_format != ImageFormat::JPEG {
+            return Err(OV2640Error::CannotSetJpegQualityOnNonJPEG);
+        }
+
+        self.write_registers(&jpeg_quality_registers(jpeg_quality)).await?;
+        self.configuration.jpeg_quality = jpeg_quality;
+        Ok(())
+    }
+
+    /// Set the horizontal mirror and vertical flip of the OV2640 Module
+    pub async fn set_flip(
+        &mut self, flip: Flip
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let reg04 = self.read_register(REG04).await?;
+        self.write_register(REG04, flip_reg04(reg04, flip)).await?;
+        self.configuration.flip = flip;
+        Ok(())
+    }
+
+    /// Set the strobe (flash light) output of the OV2640 Module
+    pub async fn set_strobe(
+        &mut self, strobe: StrobeMode
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&strobe_registers(strobe)).await?;
+        self.configuration.strobe = strobe;
+        Ok(())
+    }
+
+    /// Set the banding filter of the OV2640 Module to remove flicker from
+    /// mains powered lighting.  Writes the band steps of the current
+    /// resolution to BD50 and BD60 and updates the frequency bits of COM3 and
+    /// the banding filter bit of COM8 in the sensor bank
+    pub async fn set_banding_filter(
+        &mut self, banding_filter: BandingFilter
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&band_step_registers(&self.configuration)).await?;
+        let com3 = self.read_register(COM3).await?;
+        self.write_register(COM3, (com3 & !COM3_BAND_MASK) | com3_band_bits(banding_filter)).await?;
+        let com8 = self.read_register(COM8).await?;
+        self.write_register(COM8, banding_filter_com8(com8, banding_filter)).await?;
+        self.configuration.banding_filter = banding_filter;
+        self.manual_banding_filter = manual_banding_filter(banding_filter);
+        Ok(())
+    }
+
+    /// Turn the automatic detection of the mains frequency on or off through
+    /// the COM3 auto banding bit.  While on, the detected frequency overrides
+    /// the manual 50Hz / 60Hz selection, which takes effect again once it is
+    /// turned off.  Neither applies while the banding filter is disabled
+    pub async fn set_auto_banding(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com3 = self.read_register(COM3).await?;
+        let com3 = if on { com3 | COM3_BAND_AUTO } else { com3 & !COM3_BAND_AUTO };
+        self.write_register(COM3, com3).await?;
+        if self.configuration.banding_filter != BandingFilter::Disabled {
+            self.configuration.banding_filter = if on { BandingFilter::Auto } else { self.manual_banding_filter };
+        }
+        Ok(())
+    }
+
+    /// Replace the image with the internal color bar test pattern through the
+    /// COM7 color bar bit, to check the I2C / SPI wiring and capture pipeline
+    /// independently of the lens.  Turning it off restores normal output
+    pub async fn set_test_pattern(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com7 = self.read_register(COM7).await?;
+        let com7 = if on { com7 | COM7_COLOR_BAR } else { com7 & !COM7_COLOR_BAR };
+        self.write_register(COM7, com7).await
+    }
+
+    /// Write a register table, such as a vendor tuning list, to the OV2640
+    /// Module.  `[0xFF, bank]` entries switch between the DSP (0x00) and sensor
+    /// (0x01) banks and writing stops at a `[0xFF, 0xFF]` end marker
+    pub async fn apply_register_table(
+        &mut self, table: &[[u8; 2]]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(table).await
+    }
+
+    /// Turn night mode on or off.  In night mode the sensor inserts dummy
+    /// frames (COM1) so auto exposure can integrate for longer, trading frame
+    /// rate (down to an eighth) and motion blur for brighter low light frames
+    pub async fn set_night_mode(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com1 = self.read_register(COM1).await?;
+        if on {
+            // Night mode relies on auto exposure to lengthen the integration
+            let com8 = self.read_register(COM8).await?;
+            self.write_register(COM8, com8 | COM8_AEC_EN).await?;
+            self.write_register(COM1, com1 | COM1_NIGHT_MODE).await
+        } else {
+            self.write_register(COM1, com1 & !COM1_NIGHT_MODE).await
+        }
+    }
+
+    /// Put the sensor into (or wake it from) soft standby through the COM2
+    /// standby bit.  Standby stops the sensor clocks, cutting the draw from
+    /// tens of milliamps while streaming to around a milliamp (the hardware
+    /// power down line goes lower still), and keeps the register contents, but
+    /// `init` should be called again after waking before the next capture
+    pub async fn set_power_down(
+        &mut self, down: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com2 = self.read_register(COM2).await?;
+        if down {
+            self.write_register(COM2, com2 | COM2_STANDBY).await
+        } else {
+            self.write_register(COM2, com2 & !COM2_STANDBY).await
+        }
+    }
+
+    /// Set the exposure of the OV2640 Module.  Manual exposure turns off the
+    /// auto exposure and auto gain of COM8 and writes the exposure across the
+    /// AEC registers, `Exposure::Auto` turns them back on
+    pub async fn set_exposure(
+        &mut self, exposure: Exposure
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com8 = self.read_register(COM8).await?;
+        match exposure {
+            Exposure::Auto => self.write_register(COM8, com8 | COM8_AGC_EN | COM8_AEC_EN).await,
+            Exposure::Manual(value) => {
+                self.write_register(COM8, com8 & !(COM8_AGC_EN | COM8_AEC_EN)).await?;
+                self.write_register16(&EXPOSURE_REGISTERS, value).await
+            },
+        }
+    }
+
+    /// Read the current exposure of the OV2640 Module from the AEC registers
+    pub async fn exposure(&mut self) -> Result<u16, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        self.read_register16(&EXPOSURE_REGISTERS).await
+    }
+
+    /// Set the ceiling of the automatic gain of the OV2640 Module, limiting the
+    /// noise amplified into dark frames
+    pub async fn set_gain_ceiling(
+        &mut self, gain_ceiling: GainCeiling
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        let com9 = self.read_register(COM9).await?;
+        self.write_register(COM9, gain_ceiling_com9(com9, gain_ceiling)).await?;
+        self.configuration.gain_ceiling = gain_ceiling;
+        Ok(())
+    }
+
+    /// Set the clock divider (CLKRC) of the OV2640 Module, trading frame rate
+    /// for bandwidth on slow links.  The pixel clock is the input clock divided
+    /// by `clock_divider + 1`, only the low 6 bits are used
+    pub async fn set_clock_divider(
+        &mut self, clock_divider: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK).await?;
+        self.write_register(CLKRC, clock_divider & 0x3F).await?;
+        self.configuration.clock_divider = Some(clock_divider);
+        Ok(())
+    }
+
+    /// Crop the output to a `width` x `height` window of the sensor array
+    /// starting at (`x`, `y`).  The window must be a multiple of 4 pixels and
+    /// fit within the 1600x1200 sensor array
+    pub async fn set_window(
+        &mut self, x: u16, y: u16, width: u16, height: u16
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&window_registers(x, y, width, height)?).await
+    }
+
+    /// Read back the DSP window (HSIZE/VSIZE) and output (OUTW/OUTH) sizes and
+    /// check that the output fits within the window.  Useful as a sanity check
+    /// after hand-tuning the DSP registers
+    pub async fn validate_window_registers(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK).await?;
+        let window = [
+            self.read_register(HSIZE).await?,
+            self.read_register(VSIZE).await?,
+            self.read_register(VHYX).await?,
+            self.read_register(TEST).await?,
+        ];
+        let output = [
+            self.read_register(ZMOW).await?,
+            self.read_register(ZMOH).await?,
+            self.read_register(ZMHH).await?,
+        ];
+
+        if !window_is_consistent(window, output) {
+            return Err(OV2640Error::InconsistentWindow);
+        }
+        Ok(())
+    }
+
+    /// Soft reset the sensor to its default register values by selecting the
+    /// sensor bank, setting the COM7 reset bit and waiting the reset delay.
+    /// `init` must be called again afterwards
+    pub async fn soft_reset(&mut self, delay: &mut impl DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&SOFT_RESET_REGISTERS).await?;
+        self.current_bank = None;
+        delay.delay_ms(self.reset_delay_ms).await;
+        Ok(())
+    }
+
+    /// Reset the sensor, using a hardware reset through the reset pin if one
+    /// was provided and a soft reset otherwise.  `init` must be called again
+    /// after resetting
+    pub async fn reset(&mut self, delay: &mut impl DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(reset) = self.reset.as_mut() {
+            reset.set_low().map_err(|err| OV2640Error::PinError(err.kind()))?;
+            delay.delay_ms(10).await;
+            reset.set_high().map_err(|err| OV2640Error::PinError(err.kind()))?;
+            self.current_bank = None;
+            delay.delay_ms(self.reset_delay_ms).await;
+            Ok(())
+        } else {
+            self.soft_reset(delay).await
+        }
+    }
+
+    /// Write a register in the given bank (DSP_BANK or SENSOR_BANK) of the
+    /// sensor for tuning not covered by the configuration.  The bank is left
+    /// selected after the write, which does not affect the driver as every
+    /// setter selects its own bank first
+    pub async fn write_sensor_register(
+        &mut self, bank: u8, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(bank).await?;
+        self.write_register(register, value).await
+    }
+
+    /// Put the sensor into standby by asserting its power down line, using the
+    /// power down pin if one was provided and the ArduChip GPIO otherwise
+    pub async fn power_down(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(pwdn) = self.pwdn.as_mut() {
+            pwdn.set_high().map_err(|err| OV2640Error::PinError(err.kind()))
+        } else {
+            let gpio = self.read_spi(GPIO).await?;
+            self.write_spi(GPIO, gpio | GPIO_PWDN_MASK).await
+        }
+    }
+
+    /// Wake the sensor from standby by releasing its power down line
+    pub async fn power_up(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(pwdn) = self.pwdn.as_mut() {
+            pwdn.set_low().map_err(|err| OV2640Error::PinError(err.kind()))
+        } else {
+            let gpio = self.read_spi(GPIO).await?;
+            self.write_spi(GPIO, gpio & !GPIO_PWDN_MASK).await
+        }
+    }
+
+    /// Flush the OV2640's FIFO
+    pub async fn flush_fifo(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK).await?;
+        self.capture_state = CaptureState::Idle;
+        Ok(())
+    }
+
+    /// Abort a capture in progress, clearing the FIFO and resetting its read
+    /// pointer so the next capture starts from a clean state
+    pub async fn abort_capture(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK).await?;
+        self.write_spi(FIFO, FIFO_RDPTR_RST_MASK).await?;
+        self.capture_state = CaptureState::Idle;
+        Ok(())
+    }
+
+    /// Start capturing into the FIFO
+    pub async fn start_capture(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK).await?;
+        self.write_spi(FIFO, FIFO_START_MASK).await?;
+        self.capture_state = CaptureState::Capturing;
+        Ok(())
+    }
+
+    /// Check whether the capture is complete
+    pub async fn is_capture_done(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        let done = self.read_spi(TRIGGER).await? & CAPTURE_COMPLETE_MASK != 0;
+        if done && self.capture_state == CaptureState::Capturing {
+            self.capture_state = CaptureState::Ready;
+        }
+        Ok(done)
+    }
+
+    /// Read and decode the ArduChip trigger register, a richer alternative to
+    /// `is_capture_done`.  Reading the status does not advance the capture
+    /// state, `is_capture_done` must still be polled before reading an image
+    pub async fn status(&mut self) -> Result<Status, OV2640Error<I2CErr, SPIErr>> {
+        Ok(Status::from_bits(self.read_spi(TRIGGER).await?))
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// until `timeout_ms` milliseconds have passed
+    pub async fn capture(
+        &mut self, delay: &mut impl DelayNs, timeout_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.capture_with_poll_interval(delay, timeout_ms, CAPTURE_POLL_INTERVAL_MS).await
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// every `poll_interval_ms` milliseconds (at least 1) until `timeout_ms`
+    /// milliseconds have passed
+    pub async fn capture_with_poll_interval(
+        &mut self, delay: &mut impl DelayNs, timeout_ms: u32, poll_interval_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let poll_interval_ms = poll_interval_ms.max(1);
+        self.start_capture().await?;
+
+        let mut elapsed = 0;
+        while !self.is_capture_done().await? {
+            if elapsed >= timeout_ms {
+                return Err(OV2640Error::CaptureTimeout);
+            }
+            delay.delay_ms(poll_interval_ms).await;
+            elapsed = elapsed.saturating_add(poll_interval_ms);
+        }
+        Ok(())
+    }
+
+    /// Start a capture and return without waiting for it to complete, for use
+    /// with an external interrupt on the capture done line.  Once the interrupt
+    /// fires read the image with `collect_after_capture` (`is_capture_done` can
+    /// still be used to confirm the capture is complete)
+    pub async fn capture_nonblocking_start(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.start_capture().await
+    }
+
+    /// Read the image of a capture started with `capture_nonblocking_start`
+    /// into the provided buffer, returning the image length in bytes.  Fails
+    /// with `CaptureNotReady` if the capture has not completed
+    pub async fn collect_after_capture(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.is_capture_done().await?;
+        self.read_image(buffer).await
+    }
+
+    /// Get the length of the image in the FIFO, re-reading until two
+    /// consecutive reads agree.  Fails with `InvalidFifoLength` for an empty or
+    /// oversized FIFO
+    pub async fn image_size(&mut self) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+        let mut size = self.read_fifo_length().await?;
+        for _ in 0..FIFO_SIZE_READ_ATTEMPTS {
+            let next = self.read_fifo_length().await?;
+            if next == size {
+                break;
+            }
+            size = next;
+        }
+        check_fifo_length(size)
+    }
+
+    /// Get the approximate compression ratio of the captured image, the size
+    /// of the uncompressed frame divided by the image length in the FIFO.
+    /// Useful for tuning `JpegQuality` against the available bandwidth
+    pub async fn last_compression_ratio(&mut self) -> Result<f32, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.image_size().await?;
+        Ok(compression_ratio(&self.configuration, image_size))
+    }
+
+    /// Read the captured image into the provided buffer, returning the image
+    /// length in bytes.  Fails with `CaptureNotReady` unless `is_capture_done`
+    /// has reported a capture started since the last read as complete
+    pub async fn read_image(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let fifo_length = self.ready_image_size().await?;
+        let image_size = image_length(&self.configuration, fifo_length);
+        if buffer.len() < image_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        self.read_fifo(&mut buffer[..image_size]).await?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured JPEG into the provided buffer, stopping once the JPEG
+    /// end of image marker (0xFFD9) has been read.  The FIFO can hold padding
+    /// after the end of the JPEG that strict decoders reject, so the returned
+    /// length (including the marker) may be shorter than `image_size`
+    pub async fn read_jpeg_trimmed(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.ready_image_size().await?;
+        if buffer.len() < image_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let mut read = 0;
+        while read < image_size {
+            let end = (read + JPEG_SCAN_CHUNK_SIZE).min(image_size);
+            self.read_fifo(&mut buffer[read..end]).await?;
+
+            // Scan from the last byte of the previous chunk in case the marker
+            // is split between two chunks
+            if let Some(jpeg_end) = jpeg_end(&buffer[..end], read.saturating_sub(1)) {
+                self.capture_state = CaptureState::Idle;
+                return Ok(jpeg_end);
+            }
+            read = end;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into a buffer for DMA backed SPI transfers that
+    /// require aligned buffers and transfer lengths (e.g. 4 bytes on the STM32
+    /// and ESP32 DMA engines).  The buffer must start on an `alignment` byte
+    /// boundary and hold the image length rounded up to a multiple of
+    /// `alignment`, the bytes after the image are padding.  Returns the image
+    /// length in bytes
+    pub async fn read_image_aligned(
+        &mut self, buffer: &mut [u8], alignment: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if alignment == 0 || !(buffer.as_ptr() as usize).is_multiple_of(alignment) {
+            return Err(OV2640Error::MisalignedBuffer);
+        }
+
+        let image_size = self.ready_image_size().await?;
+        let transfer_size = image_size.next_multiple_of(alignment);
+        if buffer.len() < transfer_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        self.read_fifo(&mut buffer[..transfer_size]).await?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into the provided buffer in `block`-sized SPI
+    /// transfers for DMA engines that need fixed transfer lengths, returning
+    /// the image length in bytes.  The final block is padded with whatever the
+    /// FIFO clocks out after the image, so the buffer must hold the image
+    /// length rounded up to a multiple of `block`
+    pub async fn read_image_blocks(
+        &mut self, buffer: &mut [u8], block: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if block == 0 {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let image_size = self.ready_image_size().await?;
+        let transfer_size = image_size.next_multiple_of(block);
+        if buffer.len() < transfer_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        for chunk in buffer[..transfer_size].chunks_mut(block) {
+            self.read_fifo(chunk).await?;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into the provided buffer like `read_image`,
+    /// measuring the readout throughput with `now_us`, a free running
+    /// microsecond clock.  The result is available from `last_read_throughput`
+    pub async fn read_image_timed(
+        &mut self, buffer: &mut [u8], mut now_us: impl FnMut() -> u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let start = now_us();
+        let image_size = self.read_image(buffer).await?;
+        let elapsed = now_us().wrapping_sub(start);
+
+        self.last_read_throughput = read_throughput(image_size, elapsed);
+        Ok(image_size)
+    }
+
+    /// Get the throughput (in bytes per second) of the last `read_image_timed`
+    pub fn last_read_throughput(&self) -> Option<u32> {
+        self.last_read_throughput
+    }
+
+    /// Read the captured image into the provided buffer without the dummy byte
+    /// some ArduCam boards clock out before the image in a burst read,
+    /// returning the image length in bytes.  The dummy byte is detected from
+    /// the JPEG start of image marker, uncompressed images are returned as read
+    pub async fn read_image_exact(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.read_image(buffer).await?;
+        Ok(strip_dummy_byte(self.configuration.image_format, &mut buffer[..image_size]))
+    }
+
+    /// Read `rows` scanlines starting at `start_row` of a captured uncompressed
+    /// image into the provided buffer, returning the band length in bytes.  The
+    /// FIFO can only be read in order, so the scanlines before the band are
+    /// read (through the buffer) and discarded.  Fails with `InvalidScanlines`
+    /// for JPEG images or scanlines outside the frame
+    pub async fn read_scanlines(
+        &mut self, start_row: u16, rows: u16, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let (offset, length) = scanline_band(&self.configuration, start_row, rows)
+            .ok_or(OV2640Error::InvalidScanlines)?;
+        if buffer.len() < length {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+        if offset + length > self.ready_image_size().await? {
+            return Err(OV2640Error::InvalidScanlines);
+        }
+
+        self.write_spi(FIFO, FIFO_RDPTR_RST_MASK).await?;
+        let mut skipped = 0;
+        while skipped < offset {
+            let skip = (offset - skipped).min(length);
+            self.read_fifo(&mut buffer[..skip]).await?;
+            skipped += skip;
+        }
+        self.read_fifo(&mut buffer[..length]).await?;
+        Ok(length)
+    }
+
+    /// Read the captured JPEG into the provided buffer and check it starts with
+    /// the start of image marker (0xFFD8) and ends with the end of image marker
+    /// (0xFFD9), failing with `CorruptJpeg` otherwise.  Any FIFO padding after
+    /// the last end of image marker is left out of the returned length
+    pub async fn read_jpeg(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.read_image_exact(buffer).await?;
+        jpeg_length(&buffer[..image_size]).ok_or(OV2640Error::CorruptJpeg)
+    }
+
+    /// Read the captured image into the provided buffer, returning it as a
+    /// `Frame` with the format and dimensions of the current configuration.
+    /// The frame data leaves out the dummy byte of some ArduCam boards and any
+    /// FIFO padding after the end of a JPEG
+    pub async fn read_frame<'a>(
+        &mut self, buffer: &'a mut [u8]
+    ) -> Result<Frame<'a>, OV2640Error<I2CErr, SPIErr>> {
+        let mut image_size = self.read_image_exact(buffer).await?;
+        if self.configuration.image_format == ImageFormat::JPEG {
+            image_size = jpeg_length(&buffer[..image_size]).unwrap_or(image_size);
+        }
+
+        let (width, height) = self.configuration.frame_dimensions();
+        Ok(Frame {
+            format: self.configuration.image_format,
+            width,
+            height,
+            data: &buffer[..image_size],
+        })
+    }
+
+    /// Read the captured image into a `heapless::Vec` holding up to `N` bytes,
+    /// failing with `InvalidBufferSize` if the image is larger
+    #[cfg(feature = "heapless")]
+    pub async fn read_image_vec<const N: usize>(
+        &mut self
+    ) -> Result<heapless::Vec<u8, N>, OV2640Error<I2CErr, SPIErr>> {
+        let fifo_length = self.ready_image_size().await?;
+        let image_size = image_length(&self.configuration, fifo_length);
+        let mut image = heapless::Vec::new();
+        image.resize(image_size, 0).map_err(|_| OV2640Error::InvalidBufferSize)?;
+
+        self.read_fifo(&mut image).await?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image)
+    }
+
+    /// Capture an image and read it into the provided buffer, returning the
+    /// image length in bytes.  Fails with `CaptureTimeout` if the capture does
+    /// not complete within `timeout_ms` milliseconds
+    pub async fn snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut impl DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.capture(delay, timeout_ms).await?;
+        self.read_image(buffer).await
+    }
+
+    /// Capture an image and read it into the provided buffer like `snapshot`,
+    /// stepping the resolution down and retrying whenever the capture times
+    /// out.  Resolutions are tried from the configured resolution down through
+    /// `Resolution` in declaration order, failing with `CaptureTimeout` once the
+    /// smallest times out (or straight away for QVGA, whose resolution is
+    /// fixed).  The resolution that succeeded stays configured and is returned
+    /// with the image length
+    pub async fn snapshot_with_fallback(
+        &mut self, buffer: &mut [u8], delay: &mut impl DelayNs, timeout_ms: u32
+    ) -> Result<(usize, Resolution), OV2640Error<I2CErr, SPIErr>> {
+        loop {
+            match self.snapshot(buffer, delay, timeout_ms).await {
+                Err(OV2640Error::CaptureTimeout) => {},
+                result => return result.map(|image_size| (image_size, self.configuration.resolution)),
+            }
+            if self.configuration.image_format == ImageFormat::QVGA {
+                return Err(OV2640Error::CaptureTimeout);
+            }
+            let resolution = lower_resolution(self.configuration.resolution)
+                .ok_or(OV2640Error::CaptureTimeout)?;
+            self.set_resolution(resolution).await?;
+        }
+    }
+
+    /// Capture an image and read it into the provided buffer like `snapshot`,
+    /// timing the capture and readout with `now_ms`, a free running millisecond
+    /// clock.  Returns the image length in bytes and the elapsed milliseconds
+    pub async fn timed_snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut impl DelayNs, timeout_ms: u32,
+        mut now_ms: impl FnMut() -> u32
+    ) -> Result<(usize, u32), OV2640Error<I2CErr, SPIErr>> {
+        let start = now_ms();
+        let image_size = self.snapshot(buffer, delay, timeout_ms).await?;
+        Ok((image_size, now_ms().wrapping_sub(start)))
+    }
+
+    /// Capture and read the next frame of a stream into the provided buffer,
+    /// returning the image length in bytes.  The sensor is not reconfigured, the
+    /// FIFO is flushed before each capture so no stale data from the previous
+    /// frame is read.  Fails with `CaptureTimeout` if the capture does not
+    /// complete within `timeout_ms` milliseconds
+    pub async fn stream_next(
+        &mut self, buffer: &mut [u8], delay: &mut impl DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.flush_fifo().await?;
+        self.capture(delay, timeout_ms).await?;
+        self.read_image_exact(buffer).await
+    }
+
+    /// Read the captured image through `chunk`, passing each filled part of it
+    /// to `sink` until the whole image has been read, so large images can be
+    /// streamed out without a buffer for the whole image.  Returns the image
+    /// length in bytes
+    pub async fn read_image_chunked(
+        &mut self, chunk: &mut [u8], mut sink: impl FnMut(&[u8])
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if chunk.is_empty() {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let image_size = self.ready_image_size().await?;
+        let mut remaining = image_size;
+        while remaining > 0 {
+            let length = remaining.min(chunk.len());
+            self.read_fifo(&mut chunk[..length]).await?;
+            sink(&chunk[..length]);
+            remaining -= length;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Get the largest image in bytes the current configuration can produce,
+    /// for sizing the image buffer
+    pub fn max_image_size(&self) -> usize {
+        self.configuration.max_image_size()
+    }
+
+    /// Get the configuration currently applied by the driver
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Check whether the driver holds an I2C peripheral
+    pub fn has_i2c(&self) -> bool {
+        self.i2c.is_some()
+    }
+
+    /// Check whether the driver holds an SPI peripheral
+    pub fn has_spi(&self) -> bool {
+        self.spi.is_some()
+    }
+
+    /// Limit the number of bytes any image read transfers from the FIFO, reads
+    /// of a longer image fail with `InvalidFifoLength`.  `None` (the default)
+    /// only limits reads to the FIFO size
+    pub fn set_max_read_bytes(&mut self, max_read_bytes: Option<usize>) {
+        self.max_read_bytes = max_read_bytes;
+    }
+
+    /// Set the milliseconds waited after a soft reset, or after releasing the
+    /// reset pin, for the sensor to settle, `RESET_DELAY_MS` by default.  Some
+    /// modules need longer
+    pub fn set_reset_delay_ms(&mut self, reset_delay_ms: u32) {
+        self.reset_delay_ms = reset_delay_ms;
+    }
+
+    /// Take the SPI Peripheral from the device
+    pub fn take_spi(&mut self) -> Option<SPI> {
+        self.spi.take()
+    }
+
+    /// Take the I2C Peripheral from the device
+    pub fn take_i2c(&mut self) -> Option<I2C> {
+        self.current_bank = None;
+        self.i2c.take()
+    }
+
+    /// Take the Power Down Pin from the device
+    pub fn take_power_pin(&mut self) -> Option<PWDN> {
+        self.pwdn.take()
+    }
+
+    /// Take the Reset Pin from the device
+    pub fn take_reset_pin(&mut self) -> Option<RST> {
+        self.reset.take()
+    }
+
+    /// Reset the sensor and write the register tables for an image format
+    async fn write_image_format(
+        &mut self, image_format: ImageFormat, delay: &mut impl DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.soft_reset(delay).await?;
+
+        for table in image_format_tables(image_format) {
+            self.write_registers(table).await?;
+        }
+        self.manual_banding_filter = BandingFilter::Hz50;
+        // The format tables overwrite the byte order and lens correction bits
+        if self.configuration.byte_swap {
+            self.set_byte_swap(true).await?;
+        }
+        if self.configuration.lens_correction {
+            self.set_lens_correction(true).await?;
+        }
+        Ok(())
+    }
+
+    /// Get the length of the image in the FIFO as a buffer length, failing with
+    /// `CaptureNotReady` unless a capture has completed since the last read and
+    /// with `InvalidFifoLength` for a length above `max_read_bytes`
+    async fn ready_image_size(&mut self) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if self.capture_state != CaptureState::Ready {
+            return Err(OV2640Error::CaptureNotReady);
+        }
+        let image_size = buffer_length(self.image_size().await?)?;
+        if self.max_read_bytes.is_some_and(|max_read_bytes| image_size > max_read_bytes) {
+            return Err(OV2640Error::InvalidFifoLength);
+        }
+        Ok(image_size)
+    }
+
+    /// Read the length of the image in the FIFO from the FIFO size registers
+    async fn read_fifo_length(&mut self) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+        let len1 = self.read_spi(FIFO_SIZE_1).await?;
+        let len2 = self.read_spi(FIFO_SIZE_2).await?;
+        let len3 = self.read_spi(FIFO_SIZE_3).await?;
+
+        Ok(fifo_length(len1, len2, len3))
+    }
+
+    /// Burst read the FIFO into the buffer
+    async fn read_fifo(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            spi.transaction(&mut [
+                Operation::Write(&[FIFO_BURST]),
+                Operation::Read(buffer),
+            ]).await.map_err(OV2640Error::SpiError)
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Write to an SPI register
+    async fn write_spi(
+        &mut self, address: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            spi.write(&[address | 0x80, value]).await.map_err(OV2640Error::SpiError)
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Read from an SPI register
+    async fn read_spi(
+        &mut self, address: u8,
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            // The value is clocked out while the byte after the address is sent
+            let mut buffer = [address & 0x7F, 0x00];
+            spi.transfer_in_place(&mut buffer).await.map_err(OV2640Error::SpiError)?;
+            Ok(buffer[1])
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Write a value to a register via I2C, tracking writes to the bank select
+    /// register so redundant bank selects are skipped
+    async fn write_register(
+        &mut self, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if register == 0xFF {
+            return self.select_bank(value).await;
+        }
+        self.write_i2c(register, value).await
+    }
+
+    /// Select a register bank, skipping the write if the bank is already
+    /// selected
+    async fn select_bank(&mut self, bank: u8) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if self.current_bank == Some(bank) {
+            return Ok(());
+        }
+        // The bank is unknown if the write fails part way
+        self.current_bank = None;
+        self.write_i2c(0xFF, bank).await?;
+        self.current_bank = Some(bank);
+        Ok(())
+    }
+
+    /// Write a value to a register via I2C
+    async fn write_i2c(
+        &mut self, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(i2c) = self.i2c.as_mut() {
+            i2c.write(self.i2c_address, &[register, value]).await
+                .map_err(OV2640Error::I2CError)
+        } else {
+            Err(OV2640Error::NoI2cPeripheral)
+        }
+    }
+
+    /// Write to a set of registers via I2C
+    async fn write_registers(
+        &mut self, registers: &[[u8; 2]]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        for register in table_entries(registers) {
+            self.write_register(register[0], register[1]).await?;
+        }
+        Ok(())
+    }
+
+    /// Read the value from a register via I2C
+    async fn read_register(
+        &mut self, register: u8
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        if let Some(i2c) = self.i2c.as_mut() {
+            let mut buffer = [0u8];
+            i2c.write_read(self.i2c_address, &[register], &mut buffer).await
+                .map_err(OV2640Error::I2CError)?;
+            Ok(buffer[0])
+        } else {
+            Err(OV2640Error::NoI2cPeripheral)
+        }
+    }
+
+    /// Read a 16-bit value split across registers, `parts` giving each
+    /// register with the number of value bits held in its low bits, least
+    /// significant part first
+    async fn read_register16(
+        &mut self, parts: &[(u8, u8)]
+    ) -> Result<u16, OV2640Error<I2CErr, SPIErr>> {
+        let mut value = 0u16;
+        let mut shift = 0;
+        for &(register, bits) in parts {
+            let part = self.read_register(register).await? & register16_mask(bits);
+            value |= (part as u16) << shift;
+            shift += bits;
+        }
+        Ok(value)
+    }
+
+    /// Write a 16-bit value split across registers as described by `parts`
+    /// (see `read_register16`), leaving the other bits of each register
+    /// untouched
+    async fn write_register16(
+        &mut self, parts: &[(u8, u8)], value: u16
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let mut value = value;
+        for &(register, bits) in parts {
+            let current = if bits == 8 { 0 } else { self.read_register(register).await? };
+            self.write_register(register, register16_part(current, bits, value)).await?;
+            value = value.checked_shr(bits as u32).unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, NoopDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::digital::{
+        Mock as PinMock, State as PinState, Transaction as PinTransaction,
+    };
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    /// Poll a future to completion.  The mocks complete every operation
+    /// immediately, so the future never waits on a wake up
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// I2C transactions expected from a sequence of register accesses, leaving
+    /// out selects of the bank already selected like the driver does
+    #[derive(Default)]
+    struct ExpectedI2c {
+        // Bank selected by the expected transactions, None when unknown
+        bank: Option<u8>,
+        transactions: Vec<I2cTransaction>,
+    }
+
+    impl ExpectedI2c {
+        fn write(&mut self, register: u8, value: u8) {
+            if register == 0xFF {
+                if self.bank == Some(value) {
+                    return;
+                }
+                self.bank = Some(value);
+            }
+            self.transactions.push(I2cTransaction::write(I2C_ADDRESS, vec![register, value]));
+        }
+
+        fn read(&mut self, register: u8, value: u8) {
+            self.transactions.push(I2cTransaction::write_read(I2C_ADDRESS, vec![register], vec![value]));
+        }
+
+        fn tables(&mut self, tables: &[&[[u8; 2]]]) {
+            for table in tables {
+                for &[register, value] in table.iter().take_while(|entry| **entry != [0xFF, 0xFF]) {
+                    self.write(register, value);
+                }
+            }
+        }
+    }
+
+    fn spi_write(address: u8, value: u8) -> [SpiTransaction<u8>; 3] {
+        [
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![address | 0x80, value]),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    fn spi_read(address: u8, value: u8) -> [SpiTransaction<u8>; 3] {
+        [
+            SpiTransaction::transaction_start(),
+            SpiTransaction::transfer_in_place(vec![address & 0x7F, 0x00], vec![0x00, value]),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    #[test]
+    fn init_writes_the_default_configuration() {
+        let configuration = Configuration::default();
+        let mut expected = ExpectedI2c::default();
+        expected.tables(&[&SOFT_RESET_REGISTERS]);
+        // The bank is unknown after the reset
+        expected.bank = None;
+        expected.tables(image_format_tables(configuration.image_format));
+        // Written by both set_image_format and set_resolution
+        expected.tables(&[resolution_registers(configuration.resolution); 2]);
+        expected.tables(&[
+            light_mode_registers(configuration.light_mode),
+            &saturation_registers(configuration.saturation),
+            &brightness_registers(configuration.brightness),
+            &contrast_registers(configuration.contrast),
+            &special_effect_registers(configuration.special_effect),
+            &strobe_registers(configuration.strobe),
+            &jpeg_quality_registers(configuration.jpeg_quality),
+        ]);
+        expected.write(0xFF, SENSOR_BANK);
+        expected.read(REG04, 0x00);
+        expected.write(REG04, flip_reg04(0x00, configuration.flip));
+        expected.tables(&[&sharpness_registers::<(), ()>(configuration.sharpness).unwrap()]);
+        expected.write(0xFF, SENSOR_BANK);
+        expected.read(COM9, 0x00);
+        expected.write(COM9, gain_ceiling_com9(0x00, configuration.gain_ceiling));
+        expected.write(0xFF, DSP_BANK);
+
+        let mut i2c = I2cMock::new(&expected.transactions);
+        let mut spi = SpiMock::new(&[]);
+        let mut camera = OV2640Async::new(Some(i2c.clone()), Some(spi.clone()));
+        block_on(camera.init(&mut NoopDelay::new())).unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn capture_polls_until_the_capture_completes() {
+        let spi = [
+            &spi_write(FIFO, FIFO_CLEAR_MASK)[..],
+            &spi_write(FIFO, FIFO_START_MASK),
+            &spi_read(TRIGGER, 0x00),
+            &spi_read(TRIGGER, CAPTURE_COMPLETE_MASK),
+        ].concat();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640Async::new(Some(i2c.clone()), Some(spi.clone()));
+
+        block_on(camera.capture(&mut NoopDelay::new(), 10)).unwrap();
+        assert_eq!(camera.capture_state, CaptureState::Ready);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn failed_bank_select_forgets_the_current_bank() {
+        let mut i2c = I2cMock::new(&[
+            I2cTransaction::write(I2C_ADDRESS, vec![0xFF, DSP_BANK]),
+            I2cTransaction::write(I2C_ADDRESS, vec![QS, 0x04]),
+            I2cTransaction::write(I2C_ADDRESS, vec![0xFF, SENSOR_BANK])
+                .with_error(embedded_hal::i2c::ErrorKind::Other),
+            // The failed write may have selected either bank
+            I2cTransaction::write(I2C_ADDRESS, vec![0xFF, DSP_BANK]),
+            I2cTransaction::write(I2C_ADDRESS, vec![QS, 0x04]),
+        ]);
+        let mut spi = SpiMock::new(&[]);
+        let mut camera = OV2640Async::new(Some(i2c.clone()), Some(spi.clone()));
+
+        block_on(camera.set_jpeg_quality(JpegQuality::High)).unwrap();
+        assert!(matches!(
+            block_on(camera.set_clock_divider(0x01)),
+            Err(OV2640Error::I2CError(embedded_hal::i2c::ErrorKind::Other))
+        ));
+        assert_eq!(camera.current_bank, None);
+        block_on(camera.set_jpeg_quality(JpegQuality::High)).unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn reset_pulses_the_reset_pin() {
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&[]);
+        let mut reset = PinMock::new(&[
+            PinTransaction::set(PinState::Low),
+            PinTransaction::set(PinState::High),
+        ]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(10),
+            DelayTransaction::delay_ms(RESET_DELAY_MS),
+        ]);
+        let mut camera = OV2640Async::new_with_reset_pin(
+            Some(i2c.clone()), Some(spi.clone()), reset.clone()
+        );
+
+        block_on(camera.reset(&mut delay)).unwrap();
+        assert_eq!(camera.current_bank, None);
+        reset.done();
+        delay.done();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn power_down_toggles_the_arduchip_pwdn_bit() {
+        let spi = [
+            &spi_read(GPIO, 0x05)[..],
+            &spi_write(GPIO, 0x05 | GPIO_PWDN_MASK),
+            &spi_read(GPIO, 0x05 | GPIO_PWDN_MASK),
+            &spi_write(GPIO, 0x05),
+        ].concat();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640Async::new(Some(i2c.clone()), Some(spi.clone()));
+
+        block_on(camera.power_down()).unwrap();
+        block_on(camera.power_up()).unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_jpeg_trimmed_stops_at_the_end_of_image_marker() {
+        let spi = [
+            &spi_write(FIFO, FIFO_CLEAR_MASK)[..],
+            &spi_write(FIFO, FIFO_START_MASK),
+            &spi_read(TRIGGER, CAPTURE_COMPLETE_MASK),
+            &spi_read(FIFO_SIZE_1, 0x08),
+            &spi_read(FIFO_SIZE_2, 0x00),
+            &spi_read(FIFO_SIZE_3, 0x00),
+            &spi_read(FIFO_SIZE_1, 0x08),
+            &spi_read(FIFO_SIZE_2, 0x00),
+            &spi_read(FIFO_SIZE_3, 0x00),
+            &[
+                SpiTransaction::transaction_start(),
+                SpiTransaction::write_vec(vec![FIFO_BURST]),
+                SpiTransaction::read_vec(vec![0xFF, 0xD8, 0x01, 0xFF, 0xD9, 0x00, 0x00, 0x00]),
+                SpiTransaction::transaction_end(),
+            ],
+        ].concat();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640Async::new(Some(i2c.clone()), Some(spi.clone()));
+
+        block_on(camera.capture(&mut NoopDelay::new(), 0)).unwrap();
+        let mut buffer = [0; 8];
+        assert_eq!(block_on(camera.read_jpeg_trimmed(&mut buffer)).unwrap(), 5);
+        assert_eq!(buffer[..5], [0xFF, 0xD8, 0x01, 0xFF, 0xD9]);
+        assert_eq!(camera.capture_state, CaptureState::Idle);
+        i2c.done();
+        spi.done();
+    }
+}
@@ -2,36 +2,125 @@
 //! Configuration Options for the OV2640 Camera Module
 //!
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use crate::error::ConfigError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImageFormat {
+    // baseline sequential JPEG, the OV2640 encoder has no progressive mode
+    #[default]
     JPEG,
     QVGA,
+    RGB565,
+    // 8-bit luminance only
+    Grayscale,
+    // raw YUV422 without jpeg compression
+    YUV422,
+}
+
+impl ImageFormat {
+    /// Get the number of bytes per pixel of an uncompressed image format, or
+    /// `None` for JPEG
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        match self {
+            ImageFormat::JPEG => None,
+            ImageFormat::QVGA | ImageFormat::RGB565 | ImageFormat::YUV422 => Some(2),
+            ImageFormat::Grayscale => Some(1),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// variants are declared by increasing pixel count, which the derived
+// ordering follows
 pub enum Resolution {
+    // scaled from a square center crop of the sensor (the esp32-camera
+    // FRAMESIZE_96X96)
+    R96x96,
+    // scaled from a square center crop of the sensor (the esp32-camera
+    // FRAMESIZE_128X128)
+    R128x128,
     R160x120,
     R176x144,
     R320x240,
     R352x288,
+    // the esp32-camera FRAMESIZE_CIF
+    R400x296,
     R640x480,
     R800x600,
+    #[default]
     R1024x768,
+    R1280x960,
     R1280x1024,
     R1600x1200,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+impl Resolution {
+    /// Every resolution, ordered from smallest to largest
+    pub(crate) const ALL: [Resolution; 13] = [
+        Resolution::R96x96,
+        Resolution::R128x128,
+        Resolution::R160x120,
+        Resolution::R176x144,
+        Resolution::R320x240,
+        Resolution::R352x288,
+        Resolution::R400x296,
+        Resolution::R640x480,
+        Resolution::R800x600,
+        Resolution::R1024x768,
+        Resolution::R1280x960,
+        Resolution::R1280x1024,
+        Resolution::R1600x1200,
+    ];
+
+    /// Get the (width, height) of the resolution in pixels
+    pub fn dimensions(&self) -> (u16, u16) {
+        match self {
+            Resolution::R96x96 => (96, 96),
+            Resolution::R128x128 => (128, 128),
+            Resolution::R160x120 => (160, 120),
+            Resolution::R176x144 => (176, 144),
+            Resolution::R320x240 => (320, 240),
+            Resolution::R352x288 => (352, 288),
+            Resolution::R400x296 => (400, 296),
+            Resolution::R640x480 => (640, 480),
+            Resolution::R800x600 => (800, 600),
+            Resolution::R1024x768 => (1024, 768),
+            Resolution::R1280x960 => (1280, 960),
+            Resolution::R1280x1024 => (1280, 1024),
+            Resolution::R1600x1200 => (1600, 1200),
+        }
+    }
+
+    /// Get the width of the resolution in pixels
+    pub fn width(&self) -> u16 {
+        self.dimensions().0
+    }
+
+    /// Get the height of the resolution in pixels
+    pub fn height(&self) -> u16 {
+        self.dimensions().1
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LightMode {
+    #[default]
     Auto,
     Sunny,
     Cloudy,
     Office,
     Home,
+    // tungsten lighting, warmer than Home
+    Incandescent,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Saturation {
+    #[default]
     Saturation0,
     Saturation1,
     Saturation2,
@@ -39,8 +128,10 @@ pub enum Saturation {
     Saturation4,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Brightness {
+    #[default]
     Brightness0,
     Brightness1,
     Brightness2,
@@ -48,8 +139,10 @@ pub enum Brightness {
     Brightness4,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Contrast {
+    #[default]
     Contrast0,
     Contrast1,
     Contrast2,
@@ -57,10 +150,14 @@ pub enum Contrast {
     Contrast4,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialEffect {
+    #[default]
     Normal,
     Antique,
+    // warm brown tint, the name other ov2640 drivers give the antique tint
+    Sepia,
     Bluish,
     Greenish,
     Reddish,
@@ -69,7 +166,111 @@ pub enum SpecialEffect {
     BlackWhiteNegative,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrobeMode {
+    #[default]
+    Off,
+    // vendor supplied COM22 value
+    Custom(u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flip {
+    pub horizontal: bool,
+    pub vertical: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JpegQuality {
+    High,
+    #[default]
+    Medium,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BandingFilter {
+    Disabled,
+    // the filter the register tables leave enabled
+    #[default]
+    Hz50,
+    Hz60,
+    // detect the mains frequency from the image
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sharpness {
+    #[default]
+    Auto,
+    // edge enhancement level from 0 to MAX_SHARPNESS
+    Manual(u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WhiteBalance {
+    #[default]
+    Auto,
+    Manual { r_gain: u8, g_gain: u8, b_gain: u8 },
+}
+
+/// Lowest level accepted by the fine-grained brightness, contrast and
+/// saturation setters
+pub const MIN_LEVEL: i8 = -4;
+
+/// Highest level accepted by the fine-grained brightness, contrast and
+/// saturation setters
+pub const MAX_LEVEL: i8 = 4;
+
+/// Highest edge enhancement level of `Sharpness::Manual`
+pub const MAX_SHARPNESS: u8 = 7;
+
+/// Number of bytes in a gamma curve, fifteen curve points followed by the
+/// slope above the last point
+pub const GAMMA_CURVE_POINTS: usize = 16;
+
+/// Gamma curve written by the driver's initialization tables
+pub const DEFAULT_GAMMA_CURVE: [u8; GAMMA_CURVE_POINTS] = [
+    0x0e, 0x1a, 0x31, 0x5a, 0x69, 0x75, 0x7e, 0x88,
+    0x8f, 0x96, 0xa3, 0xaf, 0xc4, 0xd7, 0xe8, 0x20,
+];
+
+/// Gamma curve lifting the shadows of dim scenes (at the cost of noise)
+pub const LOW_LIGHT_GAMMA_CURVE: [u8; GAMMA_CURVE_POINTS] = [
+    0x46, 0x4f, 0x60, 0x7f, 0x8a, 0x93, 0x9a, 0xa2,
+    0xa7, 0xac, 0xb6, 0xbf, 0xcf, 0xdd, 0xea, 0x20,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Exposure {
+    #[default]
+    Auto,
+    // exposure in lines (AEC[15:0])
+    Manual(u16),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GainCeiling {
+    X2,
+    X4,
+    #[default]
+    X8,
+    X16,
+    X32,
+    X64,
+    X128,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Configuration {
     pub image_format: ImageFormat,
     pub resolution: Resolution,
@@ -78,9 +279,53 @@ pub struct Configuration {
     pub brightness: Brightness,
     pub contrast: Contrast,
     pub special_effect: SpecialEffect,
+    pub strobe: StrobeMode,
+    pub flip: Flip,
+    pub jpeg_quality: JpegQuality,
+    pub banding_filter: BandingFilter,
+    pub sharpness: Sharpness,
+    pub white_balance: WhiteBalance,
+    pub gain_ceiling: GainCeiling,
+    // clock divider, None keeps the divider of the resolution
+    pub clock_divider: Option<u8>,
+    // swap the byte order of RGB565 and YUV422 pixels
+    pub byte_swap: bool,
+    // lens shading correction, brightening the corners of the image
+    pub lens_correction: bool,
+}
+
+impl Default for Configuration {
+    /// The configuration built by a default `ConfigurationBuilder`
+    fn default() -> Self {
+        ConfigurationBuilder::default().build()
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+impl Configuration {
+    /// Get the (width, height) in pixels of the frames the configuration
+    /// produces, QVGA frames are always 320x240
+    pub fn frame_dimensions(&self) -> (u16, u16) {
+        match self.image_format {
+            ImageFormat::QVGA => Resolution::R320x240.dimensions(),
+            _ => self.resolution.dimensions(),
+        }
+    }
+
+    /// Get the largest image in bytes the configuration can produce, the frame
+    /// size for uncompressed formats and the whole FIFO for JPEG
+    pub fn max_image_size(&self) -> usize {
+        match self.image_format.bytes_per_pixel() {
+            Some(bytes_per_pixel) => {
+                let (width, height) = self.frame_dimensions();
+                width as usize * height as usize * bytes_per_pixel
+            },
+            None => crate::MAX_FIFO_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConfigurationBuilder {
     image_format: Option<ImageFormat>,
     resolution: Option<Resolution>,
@@ -89,6 +334,16 @@ pub struct ConfigurationBuilder {
     brightness: Option<Brightness>,
     contrast: Option<Contrast>,
     special_effect: Option<SpecialEffect>,
+    strobe: Option<StrobeMode>,
+    flip: Option<Flip>,
+    jpeg_quality: Option<JpegQuality>,
+    banding_filter: Option<BandingFilter>,
+    sharpness: Option<Sharpness>,
+    white_balance: Option<WhiteBalance>,
+    gain_ceiling: Option<GainCeiling>,
+    clock_divider: Option<u8>,
+    byte_swap: Option<bool>,
+    lens_correction: Option<bool>,
 }
 
 impl ConfigurationBuilder {
@@ -99,6 +354,16 @@ impl ConfigurationBuilder {
         self
     }
 
+    /// Shorthand for `image_format(ImageFormat::JPEG)`
+    pub fn jpeg(self) -> Self {
+        self.image_format(ImageFormat::JPEG)
+    }
+
+    /// Shorthand for `image_format(ImageFormat::QVGA)`
+    pub fn qvga(self) -> Self {
+        self.image_format(ImageFormat::QVGA)
+    }
+
     pub fn resolution(mut self, resolution: Resolution) -> Self {
         self.resolution = Some(resolution);
         self
@@ -129,6 +394,56 @@ impl ConfigurationBuilder {
         self
     }
 
+    pub fn strobe(mut self, strobe: StrobeMode) -> Self {
+        self.strobe = Some(strobe);
+        self
+    }
+
+    pub fn flip(mut self, flip: Flip) -> Self {
+        self.flip = Some(flip);
+        self
+    }
+
+    pub fn jpeg_quality(mut self, jpeg_quality: JpegQuality) -> Self {
+        self.jpeg_quality = Some(jpeg_quality);
+        self
+    }
+
+    pub fn banding_filter(mut self, banding_filter: BandingFilter) -> Self {
+        self.banding_filter = Some(banding_filter);
+        self
+    }
+
+    pub fn sharpness(mut self, sharpness: Sharpness) -> Self {
+        self.sharpness = Some(sharpness);
+        self
+    }
+
+    pub fn white_balance(mut self, white_balance: WhiteBalance) -> Self {
+        self.white_balance = Some(white_balance);
+        self
+    }
+
+    pub fn clock_divider(mut self, clock_divider: u8) -> Self {
+        self.clock_divider = Some(clock_divider);
+        self
+    }
+
+    pub fn gain_ceiling(mut self, gain_ceiling: GainCeiling) -> Self {
+        self.gain_ceiling = Some(gain_ceiling);
+        self
+    }
+
+    pub fn byte_swap(mut self, byte_swap: bool) -> Self {
+        self.byte_swap = Some(byte_swap);
+        self
+    }
+
+    pub fn lens_correction(mut self, lens_correction: bool) -> Self {
+        self.lens_correction = Some(lens_correction);
+        self
+    }
+
     pub fn build(&self) -> Configuration {
         let image_format = match self.image_format {
             Some(image_format) => image_format,
@@ -165,6 +480,41 @@ impl ConfigurationBuilder {
             None => SpecialEffect::Normal,
         };
 
+        let strobe = match self.strobe {
+            Some(strobe) => strobe,
+            None => StrobeMode::Off,
+        };
+
+        let flip = match self.flip {
+            Some(flip) => flip,
+            None => Flip { horizontal: false, vertical: false },
+        };
+
+        let jpeg_quality = match self.jpeg_quality {
+            Some(jpeg_quality) => jpeg_quality,
+            None => JpegQuality::Medium,
+        };
+
+        let banding_filter = match self.banding_filter {
+            Some(banding_filter) => banding_filter,
+            None => BandingFilter::Hz50,
+        };
+
+        let sharpness = match self.sharpness {
+            Some(sharpness) => sharpness,
+            None => Sharpness::Auto,
+        };
+
+        let white_balance = match self.white_balance {
+            Some(white_balance) => white_balance,
+            None => WhiteBalance::Auto,
+        };
+
+        let gain_ceiling = match self.gain_ceiling {
+            Some(gain_ceiling) => gain_ceiling,
+            None => GainCeiling::X8,
+        };
+
         Configuration {
             image_format,
             resolution,
@@ -173,20 +523,231 @@ impl ConfigurationBuilder {
             brightness,
             contrast,
             special_effect,
+            strobe,
+            flip,
+            jpeg_quality,
+            banding_filter,
+            sharpness,
+            white_balance,
+            gain_ceiling,
+            clock_divider: self.clock_divider,
+            byte_swap: self.byte_swap.unwrap_or(false),
+            lens_correction: self.lens_correction.unwrap_or(false),
+        }
+    }
+
+    /// Build the configuration, rejecting combinations of options that can not
+    /// be applied to the OV2640
+    pub fn try_build(&self) -> Result<Configuration, ConfigError> {
+        let configuration = self.build();
+        match configuration.image_format {
+            ImageFormat::QVGA => {
+                if self.resolution.is_some_and(|resolution| resolution != Resolution::R320x240) {
+                    return Err(ConfigError::ResolutionFixedByFormat);
+                }
+            },
+            _ => {
+                if configuration.max_image_size() > crate::MAX_FIFO_SIZE {
+                    return Err(ConfigError::ResolutionTooLargeForFifo);
+                }
+            },
+        }
+
+        if configuration.image_format != ImageFormat::JPEG && self.jpeg_quality.is_some() {
+            return Err(ConfigError::JpegQualityOnNonJPEG);
         }
+        Ok(configuration)
+    }
+
+    /// Alias of `build` to end a fluent chain
+    ///
+    /// ```
+    /// use ov2640::{ConfigurationBuilder, ImageFormat, Resolution};
+    ///
+    /// let configuration = ConfigurationBuilder::new()
+    ///     .jpeg()
+    ///     .resolution(Resolution::R640x480)
+    ///     .finish();
+    /// assert_eq!(configuration.image_format, ImageFormat::JPEG);
+    /// assert_eq!(configuration.resolution, Resolution::R640x480);
+    /// ```
+    pub fn finish(&self) -> Configuration {
+        self.build()
     }
 }
 
-impl Default for ConfigurationBuilder {
-    fn default() -> Self {
+impl From<Configuration> for ConfigurationBuilder {
+    /// Seed a builder with every field of an existing configuration
+    ///
+    /// ```
+    /// use ov2640::{Brightness, Configuration, ConfigurationBuilder};
+    ///
+    /// let base = Configuration::default();
+    /// assert_eq!(ConfigurationBuilder::from(base).build(), base);
+    ///
+    /// let brighter = ConfigurationBuilder::from(base)
+    ///     .brightness(Brightness::Brightness2)
+    ///     .build();
+    /// assert_eq!(brighter.brightness, Brightness::Brightness2);
+    /// ```
+    fn from(configuration: Configuration) -> Self {
         Self {
-            image_format: None,
-            resolution: None,
-            light_mode: None,
-            saturation: None,
-            brightness: None,
-            contrast: None,
-            special_effect: None,
+            image_format: Some(configuration.image_format),
+            resolution: Some(configuration.resolution),
+            light_mode: Some(configuration.light_mode),
+            saturation: Some(configuration.saturation),
+            brightness: Some(configuration.brightness),
+            contrast: Some(configuration.contrast),
+            special_effect: Some(configuration.special_effect),
+            strobe: Some(configuration.strobe),
+            flip: Some(configuration.flip),
+            jpeg_quality: Some(configuration.jpeg_quality),
+            banding_filter: Some(configuration.banding_filter),
+            sharpness: Some(configuration.sharpness),
+            white_balance: Some(configuration.white_balance),
+            gain_ceiling: Some(configuration.gain_ceiling),
+            clock_divider: configuration.clock_divider,
+            byte_swap: Some(configuration.byte_swap),
+            lens_correction: Some(configuration.lens_correction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::ConfigError;
+
+    #[test]
+    fn max_image_size_depends_on_the_image_format() {
+        let rgb565 = ConfigurationBuilder::new()
+            .image_format(ImageFormat::RGB565)
+            .resolution(Resolution::R320x240)
+            .build();
+        assert_eq!(rgb565.max_image_size(), 320 * 240 * 2);
+        // QVGA frames are 320x240 whatever the configured resolution
+        let qvga = ConfigurationBuilder::new().qvga().build();
+        assert_eq!(qvga.max_image_size(), 320 * 240 * 2);
+
+        let jpeg = ConfigurationBuilder::new().jpeg().resolution(Resolution::R1600x1200).build();
+        assert_eq!(jpeg.max_image_size(), crate::MAX_FIFO_SIZE);
+    }
+
+    #[test]
+    fn dimensions_match_the_variant_names() {
+        for resolution in Resolution::ALL {
+            let (width, height) = resolution.dimensions();
+            assert_eq!(format!("{:?}", resolution), format!("R{}x{}", width, height));
+            assert_eq!((resolution.width(), resolution.height()), (width, height));
+        }
+    }
+
+    #[test]
+    fn try_build_rejects_incompatible_options() {
+        let qvga = ConfigurationBuilder::new().qvga().resolution(Resolution::R1600x1200);
+        assert_eq!(qvga.try_build(), Err(ConfigError::ResolutionFixedByFormat));
+        let rgb565 = ConfigurationBuilder::new()
+            .image_format(ImageFormat::RGB565)
+            .resolution(Resolution::R1024x768);
+        assert_eq!(rgb565.try_build(), Err(ConfigError::ResolutionTooLargeForFifo));
+        let quality = ConfigurationBuilder::new().qvga().jpeg_quality(JpegQuality::High);
+        assert_eq!(quality.try_build(), Err(ConfigError::JpegQualityOnNonJPEG));
+    }
+
+    #[test]
+    fn try_build_accepts_compatible_options() {
+        let builder = ConfigurationBuilder::new()
+            .image_format(ImageFormat::RGB565)
+            .resolution(Resolution::R320x240);
+        assert_eq!(builder.try_build(), Ok(builder.build()));
+        let jpeg = ConfigurationBuilder::new()
+            .jpeg()
+            .resolution(Resolution::R1600x1200)
+            .jpeg_quality(JpegQuality::Low);
+        assert_eq!(jpeg.try_build(), Ok(jpeg.build()));
+    }
+
+    #[test]
+    fn default_configuration_matches_the_builder() {
+        assert_eq!(Configuration::default(), ConfigurationBuilder::default().build());
+        assert_eq!(Configuration::default().image_format, ImageFormat::JPEG);
+        assert_eq!(Configuration::default().resolution, Resolution::R1024x768);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn configurations_key_a_heapless_map() {
+        let indoor = ConfigurationBuilder::default().light_mode(LightMode::Home).build();
+        let mut presets = heapless::FnvIndexMap::<Configuration, &str, 4>::new();
+        presets.insert(Configuration::default(), "default").unwrap();
+        presets.insert(indoor, "indoor").unwrap();
+
+        assert_eq!(presets.get(&Configuration::default()), Some(&"default"));
+        assert_eq!(presets.get(&indoor), Some(&"indoor"));
+        assert_eq!(presets.insert(indoor, "home"), Ok(Some("indoor")));
+        assert_eq!(presets.len(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn configurations_round_trip_through_serde() {
+        let builder = ConfigurationBuilder::default()
+            .image_format(ImageFormat::RGB565)
+            .resolution(Resolution::R320x240)
+            .light_mode(LightMode::Incandescent)
+            .clock_divider(0x02);
+        let configuration = builder.build();
+
+        let json = serde_json::to_string(&configuration).unwrap();
+        assert_eq!(serde_json::from_str::<Configuration>(&json).unwrap(), configuration);
+        let json = serde_json::to_string(&builder).unwrap();
+        assert_eq!(serde_json::from_str::<ConfigurationBuilder>(&json).unwrap(), builder);
+    }
+
+    #[test]
+    fn configurations_round_trip_through_the_builder() {
+        let configuration = Configuration {
+            image_format: ImageFormat::RGB565,
+            resolution: Resolution::R320x240,
+            light_mode: LightMode::Office,
+            saturation: Saturation::Saturation0,
+            brightness: Brightness::Brightness4,
+            contrast: Contrast::Contrast1,
+            special_effect: SpecialEffect::Bluish,
+            strobe: StrobeMode::Custom(0x3C),
+            flip: Flip { horizontal: true, vertical: false },
+            jpeg_quality: JpegQuality::Low,
+            banding_filter: BandingFilter::Hz50,
+            sharpness: Sharpness::Manual(3),
+            white_balance: WhiteBalance::Manual { r_gain: 0x50, g_gain: 0x41, b_gain: 0x62 },
+            gain_ceiling: GainCeiling::X32,
+            clock_divider: Some(0x02),
+            byte_swap: true,
+            lens_correction: true,
+        };
+        assert_eq!(ConfigurationBuilder::from(configuration).build(), configuration);
+
+        let brighter = ConfigurationBuilder::from(configuration)
+            .brightness(Brightness::Brightness2)
+            .build();
+        assert_eq!(brighter, Configuration { brightness: Brightness::Brightness2, ..configuration });
+    }
+
+    #[test]
+    fn levels_and_resolutions_order_naturally() {
+        assert!(Brightness::Brightness2 < Brightness::Brightness3);
+        assert!(Contrast::Contrast0 < Contrast::Contrast4);
+        assert_eq!(Saturation::Saturation4.clamp(Saturation::Saturation1, Saturation::Saturation3), Saturation::Saturation3);
+
+        let pixels = |resolution: Resolution| {
+            let (width, height) = resolution.dimensions();
+            width as u32 * height as u32
+        };
+        for pair in Resolution::ALL.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} {:?}", pair[0], pair[1]);
+            assert!(pixels(pair[0]) < pixels(pair[1]), "{:?} {:?}", pair[0], pair[1]);
         }
+        assert_eq!(Resolution::ALL.iter().max(), Some(&Resolution::R1600x1200));
     }
-}
\ No newline at end of file
+}
@@ -1,13 +1,204 @@
 //!
 //! Error from operating the OV2640 Module
+//!
+//! The error is generic over both the I2C and SPI error types.  Applications
+//! that want a single error type should collapse both bus errors into one with
+//! `into_app_error` (for example into the embedded-hal `ErrorKind` of each bus)
+//! and then wrap the resulting `OV2640Error<E, E>` in their own error enum
 //! 
 
+use core::fmt;
+
+use embedded_hal::digital::ErrorKind;
+
+/// Errors are non exhaustive so new variants can be added without breaking
+/// downstream matches, which need a wildcard arm
+///
+/// ```
+/// use ov2640::OV2640Error;
+///
+/// let error: OV2640Error<(), ()> = OV2640Error::CaptureTimeout;
+/// let retry = match error {
+///     OV2640Error::CaptureTimeout => true,
+///     _ => false,
+/// };
+/// assert!(retry);
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum OV2640Error<I2CErr, SPIErr> {
-    CannotSetImageSizeOnNonJPEG,
+    CannotSetJpegQualityOnNonJPEG,
+    // fifo read before a capture completed
+    CaptureNotReady,
+    // capture did not complete before the timeout
+    CaptureTimeout,
+    // image is missing the jpeg start or end of image marker
+    CorruptJpeg,
+    // dsp output size is larger than the dsp input window
+    InconsistentWindow,
+    // fifo length is zero or larger than the fifo (usually a bad spi read)
+    InvalidFifoLength,
     // buffer is too small
     InvalidBufferSize,
+    // scanlines are outside the frame or the image is jpeg compressed
+    InvalidScanlines,
+    // window is not a multiple of 4 pixels or does not fit the sensor array
+    InvalidWindow,
+    // brightness, contrast or saturation level is outside MIN_LEVEL..=MAX_LEVEL,
+    // or sharpness level is above MAX_SHARPNESS
+    LevelOutOfRange,
+    // buffer is not aligned for the requested transfer
+    MisalignedBuffer,
+    // display is too small for a frame scaled to fit it
+    NoResolutionFitsDisplay,
     NoI2cPeripheral,
+    // neither an i2c nor an spi peripheral was given
+    NoPeripherals,
+    // register does not return a meaningful value when read
+    RegisterNotReadable,
+    // the image format has a fixed resolution (QVGA)
+    ResolutionNotConfigurableForFormat,
+    // uncompressed image at the resolution does not fit in the fifo
+    ResolutionTooLargeForFifo,
     I2CError(I2CErr),
     NoSpiPeripheral,
     SpiError(SPIErr),
-}
\ No newline at end of file
+    PinError(ErrorKind),
+}
+
+impl<I2CErr, SPIErr> OV2640Error<I2CErr, SPIErr> {
+    /// Former name of `ResolutionNotConfigurableForFormat`.  As an associated
+    /// constant it can be returned or compared against but not matched on,
+    /// match `ResolutionNotConfigurableForFormat` in patterns instead
+    #[deprecated(note = "renamed to `ResolutionNotConfigurableForFormat`")]
+    #[allow(non_upper_case_globals)]
+    pub const CannotSetImageSizeOnNonJPEG: Self = Self::ResolutionNotConfigurableForFormat;
+
+    /// Convert the I2C and SPI errors into other error types
+    pub fn map_bus_errors<I, S>(
+        self, map_i2c: impl FnOnce(I2CErr) -> I, map_spi: impl FnOnce(SPIErr) -> S
+    ) -> OV2640Error<I, S> {
+        match self {
+            OV2640Error::CannotSetJpegQualityOnNonJPEG => OV2640Error::CannotSetJpegQualityOnNonJPEG,
+            OV2640Error::CaptureNotReady => OV2640Error::CaptureNotReady,
+            OV2640Error::CaptureTimeout => OV2640Error::CaptureTimeout,
+            OV2640Error::CorruptJpeg => OV2640Error::CorruptJpeg,
+            OV2640Error::InconsistentWindow => OV2640Error::InconsistentWindow,
+            OV2640Error::InvalidFifoLength => OV2640Error::InvalidFifoLength,
+            OV2640Error::InvalidBufferSize => OV2640Error::InvalidBufferSize,
+            OV2640Error::InvalidScanlines => OV2640Error::InvalidScanlines,
+            OV2640Error::InvalidWindow => OV2640Error::InvalidWindow,
+            OV2640Error::LevelOutOfRange => OV2640Error::LevelOutOfRange,
+            OV2640Error::MisalignedBuffer => OV2640Error::MisalignedBuffer,
+            OV2640Error::NoResolutionFitsDisplay => OV2640Error::NoResolutionFitsDisplay,
+            OV2640Error::NoI2cPeripheral => OV2640Error::NoI2cPeripheral,
+            OV2640Error::NoPeripherals => OV2640Error::NoPeripherals,
+            OV2640Error::RegisterNotReadable => OV2640Error::RegisterNotReadable,
+            OV2640Error::ResolutionNotConfigurableForFormat => OV2640Error::ResolutionNotConfigurableForFormat,
+            OV2640Error::ResolutionTooLargeForFifo => OV2640Error::ResolutionTooLargeForFifo,
+            OV2640Error::I2CError(err) => OV2640Error::I2CError(map_i2c(err)),
+            OV2640Error::NoSpiPeripheral => OV2640Error::NoSpiPeripheral,
+            OV2640Error::SpiError(err) => OV2640Error::SpiError(map_spi(err)),
+            OV2640Error::PinError(kind) => OV2640Error::PinError(kind),
+        }
+    }
+
+    /// Convert both the I2C and SPI errors into the same application error type
+    pub fn into_app_error<E>(
+        self, map_i2c: impl FnOnce(I2CErr) -> E, map_spi: impl FnOnce(SPIErr) -> E
+    ) -> OV2640Error<E, E> {
+        self.map_bus_errors(map_i2c, map_spi)
+    }
+}
+
+impl<I2CErr: fmt::Debug, SPIErr: fmt::Debug> fmt::Display for OV2640Error<I2CErr, SPIErr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OV2640Error::CannotSetJpegQualityOnNonJPEG => write!(f, "jpeg quality can only be set for jpeg images"),
+            OV2640Error::CaptureNotReady => write!(f, "fifo read before a capture completed"),
+            OV2640Error::CaptureTimeout => write!(f, "capture did not complete before the timeout"),
+            OV2640Error::CorruptJpeg => write!(f, "image is missing the jpeg start or end of image marker"),
+            OV2640Error::InconsistentWindow => write!(f, "dsp output size is larger than the dsp input window"),
+            OV2640Error::InvalidFifoLength => write!(f, "fifo length is zero or larger than the fifo"),
+            OV2640Error::InvalidBufferSize => write!(f, "buffer is too small"),
+            OV2640Error::InvalidScanlines => write!(f, "scanlines are outside the frame or the image is jpeg compressed"),
+            OV2640Error::InvalidWindow => write!(f, "window is not a multiple of 4 pixels or does not fit the sensor array"),
+            OV2640Error::LevelOutOfRange => write!(f, "level is outside the supported range"),
+            OV2640Error::MisalignedBuffer => write!(f, "buffer is not aligned for the requested transfer"),
+            OV2640Error::NoResolutionFitsDisplay => write!(f, "display is too small for a frame scaled to fit it"),
+            OV2640Error::NoI2cPeripheral => write!(f, "no i2c peripheral"),
+            OV2640Error::NoPeripherals => write!(f, "neither an i2c nor an spi peripheral was given"),
+            OV2640Error::RegisterNotReadable => write!(f, "register does not return a meaningful value when read"),
+            OV2640Error::ResolutionNotConfigurableForFormat => write!(f, "the image format has a fixed resolution that can not be set"),
+            OV2640Error::ResolutionTooLargeForFifo => write!(f, "uncompressed image at the resolution does not fit in the fifo"),
+            OV2640Error::I2CError(err) => write!(f, "i2c error: {:?}", err),
+            OV2640Error::NoSpiPeripheral => write!(f, "no spi peripheral"),
+            OV2640Error::SpiError(err) => write!(f, "spi error: {:?}", err),
+            OV2640Error::PinError(kind) => write!(f, "pin error: {:?}", kind),
+        }
+    }
+}
+
+/// Incompatible combination of options found by `ConfigurationBuilder::try_build`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ConfigError {
+    // qvga output is always 320x240
+    ResolutionFixedByFormat,
+    // uncompressed image at the resolution does not fit in the fifo
+    ResolutionTooLargeForFifo,
+    // jpeg quality only applies to jpeg images
+    JpegQualityOnNonJPEG,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ResolutionFixedByFormat => write!(f, "qvga output is always 320x240"),
+            ConfigError::ResolutionTooLargeForFifo => write!(f, "uncompressed image at the resolution does not fit in the fifo"),
+            ConfigError::JpegQualityOnNonJPEG => write!(f, "jpeg quality only applies to jpeg images"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_prints_a_distinct_message() {
+        let errors: [OV2640Error<u8, u16>; 21] = [
+            OV2640Error::CannotSetJpegQualityOnNonJPEG,
+            OV2640Error::CaptureNotReady,
+            OV2640Error::CaptureTimeout,
+            OV2640Error::CorruptJpeg,
+            OV2640Error::InconsistentWindow,
+            OV2640Error::InvalidFifoLength,
+            OV2640Error::InvalidBufferSize,
+            OV2640Error::InvalidScanlines,
+            OV2640Error::InvalidWindow,
+            OV2640Error::LevelOutOfRange,
+            OV2640Error::MisalignedBuffer,
+            OV2640Error::NoResolutionFitsDisplay,
+            OV2640Error::NoI2cPeripheral,
+            OV2640Error::NoPeripherals,
+            OV2640Error::RegisterNotReadable,
+            OV2640Error::ResolutionNotConfigurableForFormat,
+            OV2640Error::ResolutionTooLargeForFifo,
+            OV2640Error::I2CError(0x12),
+            OV2640Error::NoSpiPeripheral,
+            OV2640Error::SpiError(0x3456),
+            OV2640Error::PinError(ErrorKind::Other),
+        ];
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        for message in &messages {
+            assert!(!message.is_empty());
+            assert_eq!(messages.iter().filter(|other| *other == message).count(), 1);
+        }
+        assert_eq!(messages[17], "i2c error: 18");
+        assert_eq!(messages[19], "spi error: 13398");
+    }
+}
@@ -2,63 +2,323 @@
 //! Driver for the OV2640 ArduCam Module
 //! 
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "image")]
+extern crate alloc;
 
 pub mod config;
-pub use config::{ImageFormat, Resolution, LightMode, Saturation, Brightness, Contrast, SpecialEffect, Configuration, ConfigurationBuilder};
+pub use config::{MIN_LEVEL, MAX_LEVEL, MAX_SHARPNESS, GAMMA_CURVE_POINTS, DEFAULT_GAMMA_CURVE, LOW_LIGHT_GAMMA_CURVE, ImageFormat, Resolution, LightMode, Saturation, Brightness, Contrast, SpecialEffect, Configuration, ConfigurationBuilder, StrobeMode, Flip, JpegQuality, BandingFilter, Sharpness, WhiteBalance, Exposure, GainCeiling};
 
 pub mod error;
-pub use error::OV2640Error;
+pub use error::{OV2640Error, ConfigError};
 
-mod register;
+pub mod register;
 use register::*;
+use register::{arduchip::*, dsp::*, sensor::*};
+pub use register::RegisterTable;
+
+mod sequence;
+use sequence::*;
+
+mod access;
+use access::{RegisterAccess, modify_register, update_bits};
+
+pub mod readout;
+pub use readout::{Frame, FrameReader, rewrite_quantization_tables};
 
-use embedded_hal::{i2c::{I2c, SevenBitAddress}, spi::SpiDevice, delay::DelayNs};
+pub mod typestate;
+pub use typestate::{OV2640Typed, NoI2c, NoSpi};
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::OV2640Async;
+
+use core::convert::Infallible;
+
+use embedded_hal::{i2c::{I2c, SevenBitAddress}, spi::{SpiDevice, Operation}, delay::DelayNs, digital::{self, OutputPin, Error as _}};
 
 /// Maximum Frame Buffer Size (384KBytes)
 pub const MAX_FIFO_SIZE: usize = 0x5FFFF;
-/// Address of the OV2640
-pub const I2C_ADDRESS: u8 = 0x60;
+/// Default (7-bit) I2C address of the OV2640, the 0x60 SCCB write address
+/// without the read / write bit
+pub const I2C_ADDRESS: u8 = 0x30;
+
+/// Register bank select value for the DSP registers
+pub const DSP_BANK: u8 = 0x00;
+/// Register bank select value for the sensor registers
+pub const SENSOR_BANK: u8 = 0x01;
 
 /// Clear FIFO MASK
-pub const FIFO_CLEAR_MASK: u8 = 0x00;
+pub const FIFO_CLEAR_MASK: u8 = 0x01;
 /// Begin Capture FIFO Mask
-pub const FIFO_START_MASK: u8 = 0x00;
+pub const FIFO_START_MASK: u8 = 0x02;
+/// Reset FIFO Read Pointer Mask
+pub const FIFO_RDPTR_RST_MASK: u8 = 0x10;
 /// Capture Complete Mask
 pub const CAPTURE_COMPLETE_MASK: u8 = 0x08;
+/// VSYNC Level Mask
+pub const VSYNC_MASK: u8 = 0x01;
+/// Shutter Button Pressed Mask
+pub const SHUTTER_MASK: u8 = 0x02;
 /// Allow FIFO to be read at once
 pub const FIFO_BURST: u8 = 0x3C;
+/// Sensor Power Down GPIO Mask (1 = standby)
+pub const GPIO_PWDN_MASK: u8 = 0x02;
+
+/// Maximum number of times the FIFO size is re-read waiting for a stable value
+const FIFO_SIZE_READ_ATTEMPTS: usize = 4;
+/// Number of bytes burst read from the FIFO between scans for the JPEG EOI
+const JPEG_SCAN_CHUNK_SIZE: usize = 512;
+/// Milliseconds between polls of the capture complete flag used by `capture`,
+/// for the lowest latency.  A JPEG frame takes tens of milliseconds to capture,
+/// so passing 5-10ms to `capture_with_poll_interval` cuts the SPI traffic with
+/// little added latency
+pub const CAPTURE_POLL_INTERVAL_MS: u32 = 1;
+/// Default milliseconds waited after a soft reset for the sensor to settle
+pub const RESET_DELAY_MS: u32 = 100;
+
+/// Get the registers of a bank that can be meaningfully read back
+///
+/// The OV2640 does not reject reads of undocumented or reserved addresses, it
+/// simply returns whatever is on the internal bus, so reads outside of this
+/// list are garbage.  The BPDATA (0x7D) and gamma data ports of the DSP bank
+/// are indirect and do not read back the values written through them
+pub fn readable_registers(bank: u8) -> &'static [u8] {
+    match bank {
+        DSP_BANK => &DSP_READABLE_REGISTERS,
+        SENSOR_BANK => &SENSOR_READABLE_REGISTERS,
+        _ => &[],
+    }
+}
+
+/// Placeholder for a control pin that is not connected
+pub struct NoPin;
+
+impl digital::ErrorType for NoPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> { Ok(()) }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> { Ok(()) }
+}
+
+/// Result of probing the interfaces of an OV2640 Module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ModuleInfo {
+    // The sensor answered over I2C with an OV2640 product ID
+    pub i2c_ok: bool,
+    // The ArduChip test register read back over SPI
+    pub spi_ok: bool,
+    // Raw product ID read over I2C, None if the read failed
+    pub chip_id: Option<u16>,
+}
+
+/// Status bits of the ArduChip trigger register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    // Level of the sensor VSYNC line
+    pub vsync: bool,
+    // The shutter button of the module is pressed
+    pub shutter: bool,
+    // The last capture into the FIFO is complete
+    pub capture_done: bool,
+}
+
+impl Status {
+    /// Decode the value of the trigger register, ignoring undocumented bits
+    ///
+    /// ```
+    /// use ov2640::Status;
+    ///
+    /// let status = Status::from_bits(0x09);
+    /// assert!(status.vsync && status.capture_done && !status.shutter);
+    /// ```
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            vsync: bits & VSYNC_MASK != 0,
+            shutter: bits & SHUTTER_MASK != 0,
+            capture_done: bits & CAPTURE_COMPLETE_MASK != 0,
+        }
+    }
+}
+
+/// A capture that has been started but not yet read, borrowing the driver
+/// until the capture completes
+pub struct CaptureInProgress<'a, I2C, SPI, PWDN = NoPin, RST = NoPin> {
+    // Driver performing the capture
+    driver: &'a mut OV2640<I2C, SPI, PWDN, RST>,
+}
+
+impl<I2C, SPI, PWDN, RST, I2CErr, SPIErr> CaptureInProgress<'_, I2C, SPI, PWDN, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin,
+    RST: OutputPin {
+    /// Check whether the capture is complete, returning the length of the image
+    /// in the FIFO once it is
+    pub fn poll(&mut self) -> Result<Option<usize>, OV2640Error<I2CErr, SPIErr>> {
+        if !self.driver.is_capture_done()? {
+            return Ok(None);
+        }
+        Ok(Some(buffer_length(self.driver.image_size()?)?))
+    }
+}
 
-pub struct OV2640<I2C, SPI> {
+pub struct OV2640<I2C, SPI, PWDN = NoPin, RST = NoPin> {
     // Configuration
     configuration: Configuration,
     // I2C Peripheral
     i2c: Option<I2C>,
     // SPI Peripheral
     spi: Option<SPI>,
+    // Power Down Pin (when not controlled by the ArduChip)
+    pwdn: Option<PWDN>,
+    // Reset Pin
+    reset: Option<RST>,
+    // I2C Address
+    i2c_address: SevenBitAddress,
+    // State of the last capture
+    capture_state: CaptureState,
+    // Register bank last selected, None when unknown
+    current_bank: Option<u8>,
+    // Banding filter COM3 selects while auto banding is off
+    manual_banding_filter: BandingFilter,
+    // Largest FIFO read allowed, None for no limit beyond the FIFO size
+    max_read_bytes: Option<usize>,
+    // Milliseconds waited after a soft reset
+    reset_delay_ms: u32,
+    // Bytes per second of the last timed image read
+    last_read_throughput: Option<u32>,
+}
+
+impl<I2C, SPI, PWDN, RST> OV2640<I2C, SPI, PWDN, RST> {
+    fn from_parts(
+        configuration: Configuration,
+        i2c: Option<I2C>,
+        spi: Option<SPI>,
+        pwdn: Option<PWDN>,
+        reset: Option<RST>,
+    ) -> Self {
+        Self {
+            configuration,
+            i2c,
+            spi,
+            pwdn,
+            reset,
+            i2c_address: I2C_ADDRESS,
+            capture_state: CaptureState::Idle,
+            current_bank: None,
+            manual_banding_filter: manual_banding_filter(configuration.banding_filter),
+            max_read_bytes: None,
+            reset_delay_ms: RESET_DELAY_MS,
+            last_read_throughput: None,
+        }
+    }
+
+    /// Get the configuration currently applied by the driver
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Check whether the driver holds an I2C peripheral
+    pub fn has_i2c(&self) -> bool {
+        self.i2c.is_some()
+    }
+
+    /// Check whether the driver holds an SPI peripheral
+    pub fn has_spi(&self) -> bool {
+        self.spi.is_some()
+    }
+
+    /// Limit the number of bytes any image read transfers from the FIFO, reads
+    /// of a longer image fail with `InvalidFifoLength`.  `None` (the default)
+    /// only limits reads to the FIFO size
+    pub fn set_max_read_bytes(&mut self, max_read_bytes: Option<usize>) {
+        self.max_read_bytes = max_read_bytes;
+    }
+
+    /// Set the milliseconds waited after a soft reset, or after releasing the
+    /// reset pin, for the sensor to settle, `RESET_DELAY_MS` by default.  Some
+    /// modules need longer
+    pub fn set_reset_delay_ms(&mut self, reset_delay_ms: u32) {
+        self.reset_delay_ms = reset_delay_ms;
+    }
 }
 
-impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
+impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI, NoPin, NoPin> where
     I2C: I2c<SevenBitAddress, Error=I2CErr>,
     SPI: SpiDevice<u8, Error=SPIErr> {
     /// Initialize a new OV2640 Driver
     pub fn new(i2c: Option<I2C>, spi: Option<SPI>) -> Self {
-        Self {
-            configuration: ConfigurationBuilder::default().build(),
-            i2c,
-            spi,
+        Self::with_configuration(ConfigurationBuilder::default().build(), i2c, spi)
+    }
+
+    /// Initialize a new OV2640 Driver, failing with `NoPeripherals` if neither
+    /// an I2C nor an SPI peripheral is given
+    pub fn try_new(
+        i2c: Option<I2C>, spi: Option<SPI>
+    ) -> Result<Self, OV2640Error<I2CErr, SPIErr>> {
+        if i2c.is_none() && spi.is_none() {
+            return Err(OV2640Error::NoPeripherals);
         }
+        Ok(Self::new(i2c, spi))
     }
 
     /// Initialize a new OV2640 Driver with given configuration
     pub fn with_configuration(
         configuration: Configuration, i2c: Option<I2C>, spi: Option<SPI>
     ) -> Self {
-        Self {
-            configuration,
-            i2c,
-            spi,
-        }
+        Self::from_parts(configuration, i2c, spi, None, None)
+    }
+
+    /// Initialize a new OV2640 Driver at a non default I2C address, for boards
+    /// that strap the address differently or several modules on one I2C bus
+    pub fn new_with_address(
+        i2c: Option<I2C>, spi: Option<SPI>, i2c_address: SevenBitAddress
+    ) -> Self {
+        let mut ov2640 = Self::new(i2c, spi);
+        ov2640.i2c_address = i2c_address;
+        ov2640
+    }
+}
+
+impl<I2C, SPI, PWDN, I2CErr, SPIErr> OV2640<I2C, SPI, PWDN, NoPin> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin {
+    /// Initialize a new OV2640 Driver whose sensor power down line is wired to
+    /// a GPIO pin instead of the ArduChip
+    pub fn new_with_power_pin(i2c: Option<I2C>, spi: Option<SPI>, pwdn: PWDN) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, Some(pwdn), None)
+    }
+}
+
+impl<I2C, SPI, RST, I2CErr, SPIErr> OV2640<I2C, SPI, NoPin, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    RST: OutputPin {
+    /// Initialize a new OV2640 Driver whose sensor reset line is wired to a
+    /// GPIO pin
+    pub fn new_with_reset_pin(i2c: Option<I2C>, spi: Option<SPI>, reset: RST) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, None, Some(reset))
+    }
+}
+
+impl<I2C, SPI, PWDN, RST, I2CErr, SPIErr> OV2640<I2C, SPI, PWDN, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin,
+    RST: OutputPin {
+    /// Initialize a new OV2640 Driver whose sensor power down and reset lines
+    /// are both wired to GPIO pins
+    pub fn new_with_pins(i2c: Option<I2C>, spi: Option<SPI>, pwdn: PWDN, reset: RST) -> Self {
+        Self::from_parts(ConfigurationBuilder::default().build(), i2c, spi, Some(pwdn), Some(reset))
     }
 
     /// Check that I2C is correctly connected to the OV2640 Module
@@ -70,17 +330,46 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
 
     /// Check that SPI is correctly connected to the OV2640 Module
     pub fn spi_connected(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x01)?;
+        let (high, low) = self.product_id()?;
+        // Check a valid chip ID was found
+        Ok(is_ov2640_product_id(high, low))
+    }
+
+    /// Probe both interfaces of the module for bring-up diagnostics, reporting
+    /// which responded and the product ID.  Unlike `i2c_connected` and
+    /// `spi_connected` an interface that fails is reported rather than
+    /// returning its bus error
+    pub fn probe(&mut self) -> Result<ModuleInfo, OV2640Error<I2CErr, SPIErr>> {
+        let spi_ok = match self.write_spi(TEST_REGISTER, 0x52) {
+            Ok(()) => self.read_spi(TEST_REGISTER).is_ok_and(|value| value == 0x52),
+            Err(_) => false,
+        };
+        let product_id = self.product_id().ok();
+        Ok(ModuleInfo {
+            i2c_ok: product_id.is_some_and(|(high, low)| is_ov2640_product_id(high, low)),
+            spi_ok,
+            chip_id: product_id.map(|(high, low)| u16::from_be_bytes([high, low])),
+        })
+    }
+
+    /// Read the raw (high, low) product ID of the sensor, 0x26 low with a high
+    /// byte of 0x41 or 0x42 depending on the silicon revision
+    pub fn product_id(&mut self) -> Result<(u8, u8), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK)?;
         let high = self.read_register(CHIP_ID_HIGH)?;
         let low = self.read_register(CHIP_ID_LOW)?;
-        // Check a valid chip ID was found
-        Ok(
-            low == 0x26 &&
-            (high == 0x41 || high == 0x42)
-        )
+        Ok((high, low))
     }
 
-    /// Initialize the OV2640 Driver with its configuration
+    /// Read the average luminance of the last frame as measured by the auto
+    /// exposure engine (YAVG), a cheap estimate of the ambient light level
+    pub fn average_luminance(&mut self) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK)?;
+        self.read_register(YAVG)
+    }
+
+    /// Initialize the OV2640 Driver with its configuration, leaving the DSP
+    /// bank selected
     pub fn init(&mut self, delay: &mut dyn DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
         self.set_image_format(self.configuration.image_format, delay)?;
         self.set_resolution(self.configuration.resolution)?;
@@ -88,7 +377,42 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
         self.set_saturation(self.configuration.saturation)?;
         self.set_brightness(self.configuration.brightness)?;
         self.set_contrast(self.configuration.contrast)?;
-        self.set_special_effect(self.configuration.special_effect)
+        self.set_special_effect(self.configuration.special_effect)?;
+        self.set_strobe(self.configuration.strobe)?;
+        if self.configuration.image_format == ImageFormat::JPEG {
+            self.set_jpeg_quality(self.configuration.jpeg_quality)?;
+        }
+        self.set_flip(self.configuration.flip)?;
+        // The image format tables already leave the 50Hz filter enabled
+        if self.configuration.banding_filter != BandingFilter::Hz50 {
+            self.set_banding_filter(self.configuration.banding_filter)?;
+        }
+        self.set_sharpness(self.configuration.sharpness)?;
+        // Auto white balance would undo a light mode preset
+        if let WhiteBalance::Manual { .. } = self.configuration.white_balance {
+            self.set_white_balance(self.configuration.white_balance)?;
+        }
+        self.set_gain_ceiling(self.configuration.gain_ceiling)?;
+        if let Some(clock_divider) = self.configuration.clock_divider {
+            self.set_clock_divider(clock_divider)?;
+        }
+        self.select_bank(DSP_BANK)
+    }
+
+    /// Initialize the OV2640 Driver, retrying up to `retries` more times when
+    /// an I2C error occurs (such as the NAK some modules give the first write
+    /// after power up).  Returns the last error once the retries run out
+    pub fn init_with_retries(
+        &mut self, delay: &mut dyn DelayNs, retries: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let mut result = self.init(delay);
+        for _ in 0..retries {
+            match result {
+                Err(OV2640Error::I2CError(_)) => result = self.init(delay),
+                _ => break,
+            }
+        }
+        result
     }
 
     /// Set the configuration of the OV2640 Driver
@@ -99,24 +423,33 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
         self.init(delay)
     }
 
+    /// Read back the image format and output size registers and compare them
+    /// against the values `init` wrote, returning false if the sensor did not
+    /// latch them (or they were changed since, for example by `set_window`)
+    pub fn verify_configuration(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK)?;
+        for register in VERIFIED_DSP_REGISTERS {
+            if let Some(expected) = expected_dsp_register(&self.configuration, register) {
+                if self.read_register(register)? != expected {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
     /// Set the image format for the OV2640 Module
     pub fn set_image_format(
         &mut self, image_format: ImageFormat, delay: &mut dyn DelayNs
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x01)?;
-        self.write_register(0x12, 0x80)?;
-        delay.delay_ms(100);
-
-        match image_format {
-            ImageFormat::JPEG => {
-                self.write_registers(&JPEG_INIT_REGISTER)?;
-                self.write_registers(&YUV422_REGISTERS)?;
-                self.write_registers(&JPEG_REGISTERS)?;
-                self.write_register(0xFF, 0x01)?;
-                self.write_register(0x15, 0x00)?;
-                self.set_resolution(self.configuration.resolution)?;
-            },
-            ImageFormat::QVGA => self.write_registers(&QVGA_REGISTERS)?,
+        let has_resolution = image_format != ImageFormat::QVGA;
+        if has_resolution {
+            check_resolution(image_format, self.configuration.resolution)?;
+        }
+
+        self.write_image_format(image_format, delay)?;
+        if has_resolution {
+            self.write_resolution(self.configuration.resolution)?;
         }
         self.configuration.image_format = image_format;
         Ok(())
@@ -126,92 +459,85 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
     pub fn set_resolution(
         &mut self, resolution: Resolution
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        if self.configuration.image_format != ImageFormat::JPEG {
-            return Err(OV2640Error::CannotSetImageSizeOnNonJPEG);
-        }
+        check_resolution(self.configuration.image_format, resolution)?;
 
-        match resolution {
-            Resolution::R160x120 => self.write_registers(&JPEG_160x120_REGISTERS)?,
-            Resolution::R176x144 => self.write_registers(&JPEG_176x144_REGISTERS)?,
-            Resolution::R320x240 => self.write_registers(&JPEG_320x240_REGISTERS)?,
-            Resolution::R352x288 => self.write_registers(&JPEG_352x288_REGISTERS)?,
-            Resolution::R640x480 => self.write_registers(&JPEG_640x480_REGISTERS)?,
-            Resolution::R800x600 => self.write_registers(&JPEG_800x600_REGISTERS)?,
-            Resolution::R1024x768 => self.write_registers(&JPEG_1024x768_REGISTERS)?,
-            Resolution::R1280x1024 => self.write_registers(&JPEG_1280x1024_REGISTERS)?,
-            Resolution::R1600x1200 => self.write_registers(&JPEG_1600x1200_REGISTERS)?,
-        }
+        self.write_resolution(resolution)?;
+        self.configuration.resolution = resolution;
+        Ok(())
+    }
+
+    /// Set the image format and resolution of the OV2640 Module together with
+    /// a single reset, so the module is never left with a resolution from the
+    /// previous image format
+    pub fn set_format_and_resolution(
+        &mut self, image_format: ImageFormat, resolution: Resolution, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        check_resolution(image_format, resolution)?;
+
+        self.write_image_format(image_format, delay)?;
+        self.write_resolution(resolution)?;
+        self.configuration.image_format = image_format;
         self.configuration.resolution = resolution;
         Ok(())
     }
 
+    /// Configure the OV2640 Module to output RGB565 frames for a display.  The
+    /// resolution with the aspect ratio closest to the display's is chosen and
+    /// the DSP output is scaled down, keeping that aspect ratio, to fit within
+    /// the display.  Returns the resolution chosen and the size of the frames.
+    ///
+    /// The configuration keeps the resolution chosen, so `verify_configuration`
+    /// reports the output size as changed when the frames are scaled
+    pub fn set_for_display(
+        &mut self, display_width: u16, display_height: u16, delay: &mut dyn DelayNs
+    ) -> Result<(Resolution, (u16, u16)), OV2640Error<I2CErr, SPIErr>> {
+        let (resolution, size) = display_resolution(display_width, display_height)
+            .ok_or(OV2640Error::NoResolutionFitsDisplay)?;
+
+        self.set_format_and_resolution(ImageFormat::RGB565, resolution, delay)?;
+        if size != resolution.dimensions() {
+            self.write_registers(&output_size_registers(size.0, size.1))?;
+        }
+        Ok((resolution, size))
+    }
+
     /// Set the light mode of the OV2640 Module
     pub fn set_light_mode(
         &mut self, light_mode: LightMode,
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x00)?;
-        match light_mode {
-            LightMode::Auto => self.write_register(0xC7, 0x00)?,
-            LightMode::Sunny => {
-                self.write_register(0xC7, 0x40)?;
-                self.write_register(0xCC, 0x5E)?;
-                self.write_register(0xCD, 0x41)?;
-                self.write_register(0xCE, 0x54)?;
-            },
-            LightMode::Cloudy => {
-                self.write_register(0xC7, 0x40)?;
-                self.write_register(0xCC, 0x65)?;
-                self.write_register(0xCD, 0x41)?;
-                self.write_register(0xCE, 0x4F)?;
-            },
-            LightMode::Office => {
-                self.write_register(0xC7, 0x40)?;
-                self.write_register(0xCC, 0x52)?;
-                self.write_register(0xCD, 0x41)?;
-                self.write_register(0xCE, 0x6)?;
-            },
-            LightMode::Home => {
-                self.write_register(0xC7, 0x40)?;
-                self.write_register(0xCC, 0x42)?;
-                self.write_register(0xCD, 0x3F)?;
-                self.write_register(0xCE, 0x71)?;
+        self.write_registers(light_mode_registers(light_mode))?;
+        self.configuration.light_mode = light_mode;
+        Ok(())
+    }
+
+    /// Set the white balance of the OV2640 Module, either automatic or with
+    /// manual red, green and blue gains.  This overrides the light mode
+    pub fn set_white_balance(
+        &mut self, white_balance: WhiteBalance
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        match white_balance {
+            WhiteBalance::Auto => self.write_registers(light_mode_registers(LightMode::Auto))?,
+            WhiteBalance::Manual { r_gain, g_gain, b_gain } => {
+                self.write_registers(&manual_white_balance_registers(r_gain, g_gain, b_gain))?
             },
         }
-        self.configuration.light_mode = light_mode;
+        self.configuration.white_balance = white_balance;
         Ok(())
     }
 
+    /// Set the raw red, green and blue white balance gains (0xCC, 0xCD and
+    /// 0xCE of the DSP bank), turning off auto white balance
+    pub fn set_white_balance_raw(
+        &mut self, regs: [u8; 3]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.set_white_balance(WhiteBalance::Manual { r_gain: regs[0], g_gain: regs[1], b_gain: regs[2] })
+    }
+
     /// Set the saturation of the OV2640 Module
     pub fn set_saturation(
         &mut self, saturation: Saturation
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x00)?;
-        self.write_register(0x7C, 0x00)?;
-        self.write_register(0x7D, 0x02)?;
-        self.write_register(0x7C, 0x04)?;
-
-        match saturation {
-            Saturation::Saturation0 => {
-                self.write_register(0x7D, 0x68)?;
-                self.write_register(0x7D, 0x68)?;
-            },
-            Saturation::Saturation1 => {
-                self.write_register(0x7D, 0x58)?;
-                self.write_register(0x7D, 0x58)?;
-            },
-            Saturation::Saturation2 => {
-                self.write_register(0x7D, 0x48)?;
-                self.write_register(0x7D, 0x48)?;
-            },
-            Saturation::Saturation3 => {
-                self.write_register(0x7D, 0x38)?;
-                self.write_register(0x7D, 0x38)?;
-            },
-            Saturation::Saturation4 => {
-                self.write_register(0x7D, 0x28)?;
-                self.write_register(0x7D, 0x28)?;
-            }
-        }
+        self.write_registers(&saturation_registers(saturation))?;
         self.configuration.saturation = saturation;
         Ok(())
     }
@@ -220,20 +546,7 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
     pub fn set_brightness(
         &mut self, brightness: Brightness
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x00)?;
-        self.write_register(0x7C, 0x00)?;
-        self.write_register(0x7D, 0x04)?;
-        self.write_register(0x7C, 0x09)?;
-
-        match brightness {
-            Brightness::Brightness0 => self.write_register(0x7D, 0x40)?,
-            Brightness::Brightness1 => self.write_register(0x7D, 0x30)?,
-            Brightness::Brightness2 => self.write_register(0x7D, 0x20)?,
-            Brightness::Brightness3 => self.write_register(0x7D, 0x10)?,
-            Brightness::Brightness4 => self.write_register(0x7D, 0x00)?,
-        }
-
-        self.write_register(0x7D, 0x00)?;
+        self.write_registers(&brightness_registers(brightness))?;
         self.configuration.brightness = brightness;
         Ok(())
     }
@@ -241,213 +554,2454 @@ impl<I2C, SPI, I2CErr, SPIErr> OV2640<I2C, SPI> where
     pub fn set_contrast(
         &mut self, contrast: Contrast
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x00)?;
-        self.write_register(0x7C, 0x00)?;
-        self.write_register(0x7D, 0x04)?;
-        self.write_register(0x7C, 0x07)?;
-        self.write_register(0x7D, 0x20)?;
-
-        match contrast {
-            Contrast::Contrast0 => {
-                self.write_register(0x7D, 0x28)?;
-                self.write_register(0x7D, 0x0C)?;
-            },
-            Contrast::Contrast1 => {
-                self.write_register(0x7D, 0x24)?;
-                self.write_register(0x7D, 0x16)?;
-            },
-            Contrast::Contrast2 => {
-                self.write_register(0x7D, 0x20)?;
-                self.write_register(0x7D, 0x20)?;
-            },
-            Contrast::Contrast3 => {
-                self.write_register(0x7D, 0x20)?;
-                self.write_register(0x7D, 0x2A)?;
-            },
-            Contrast::Contrast4 => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7D, 0x34)?;
-            }
-        }
-
-        self.write_register(0x7D, 0x06)?;
+        self.write_registers(&contrast_registers(contrast))?;
         self.configuration.contrast = contrast;
         Ok(())
     }
 
+    /// Set the saturation as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Saturation2` and each `Saturation` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub fn set_saturation_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&saturation_level_registers(check_level(level)?))
+    }
+
+    /// Set the brightness as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Brightness2` and each `Brightness` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub fn set_brightness_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&brightness_level_registers(check_level(level)?))
+    }
+
+    /// Set the contrast as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Contrast2` and each `Contrast` step is two levels.  The level is not
+    /// recorded in the configuration
+    pub fn set_contrast_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&contrast_level_registers(check_level(level)?))
+    }
+
+    /// Set a custom gamma curve of the OV2640 Module, see `DEFAULT_GAMMA_CURVE`
+    /// and `LOW_LIGHT_GAMMA_CURVE` for presets
+    pub fn set_gamma_curve(
+        &mut self, points: &[u8; GAMMA_CURVE_POINTS]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&gamma_curve_registers(points))
+    }
+
     /// Set the special effect used by the OV2640 Module
     pub fn set_special_effect(
         &mut self, special_effect: SpecialEffect
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_register(0xFF, 0x00)?;
-        self.write_register(0x7C, 0x00)?;
-
-        match special_effect {
-            SpecialEffect::Antique => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x40)?;
-                self.write_register(0x7D, 0xA6)?;
-            },
-            SpecialEffect::Bluish => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0xA0)?;
-                self.write_register(0x7D, 0x40)?;
-            },
-            SpecialEffect::Greenish => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x40)?;
-                self.write_register(0x7D, 0x40)?;
-            },
-            SpecialEffect::Reddish => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x40)?;
-                self.write_register(0x7D, 0xC0)?;
-            },
-            SpecialEffect::BlackWhite => {
-                self.write_register(0x7D, 0x18)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x80)?;
-                self.write_register(0x7D, 0x80)?;
-            },
-            SpecialEffect::Negative => {
-                self.write_register(0x7D, 0x40)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x80)?;
-                self.write_register(0x7D, 0x80)?;
-            },
-            SpecialEffect::BlackWhiteNegative => {
-                self.write_register(0x7D, 0x58)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x80)?;
-                self.write_register(0x7D, 0x80)?;
-            },
-            SpecialEffect::Normal => {
-                self.write_register(0x7D, 0x00)?;
-                self.write_register(0x7C, 0x05)?;
-                self.write_register(0x7D, 0x80)?;
-                self.write_register(0x7D, 0x80)?;
-            }
-        }
-
+        self.write_registers(&special_effect_registers(special_effect))?;
         self.configuration.special_effect = special_effect;
         Ok(())
     }
 
-    /// Flush the OV2640's FIFO
-    pub fn flush_fifo(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_spi(FIFO, FIFO_CLEAR_MASK)
+    /// Tint the image with fixed U and V chroma values, the effect behind the
+    /// tint presets of `SpecialEffect`: Antique / Sepia is (0x40, 0xA6), Bluish
+    /// (0xA0, 0x40), Greenish (0x40, 0x40), Reddish (0x40, 0xC0) and BlackWhite
+    /// (0x80, 0x80).  The tint is not recorded in the configuration
+    pub fn set_special_effect_raw(
+        &mut self, u: u8, v: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&special_effect_uv_registers(0x18, u, v))
     }
 
-    /// Start capturing into the FIFO
-    pub fn start_capture(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        self.write_spi(FIFO, FIFO_CLEAR_MASK)?;
-        self.write_spi(FIFO, FIFO_START_MASK)
+    /// Set the sharpness (edge enhancement) of the OV2640 Module, failing with
+    /// `LevelOutOfRange` for a manual level above `MAX_SHARPNESS`
+    pub fn set_sharpness(
+        &mut self, sharpness: Sharpness
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&sharpness_registers(sharpness)?)?;
+        self.configuration.sharpness = sharpness;
+        Ok(())
     }
 
-    /// Check whether the capture is complete
-    pub fn is_capture_done(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
-        Ok(self.read_spi(TRIGGER)? & CAPTURE_COMPLETE_MASK != 0)
+    /// Swap the byte order of each RGB565 or YUV422 pixel in the DSP, for
+    /// displays expecting the low byte first
+    pub fn set_byte_swap(
+        &mut self, swap: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        update_bits(self, DSP_BANK, IMAGE_MODE, IMAGE_MODE_BYTE_SWAP, swap)?;
+        self.configuration.byte_swap = swap;
+        Ok(())
     }
 
-    /// Get the length of the image in the FIFO
-    pub fn image_size(&mut self) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
-        let len1 = self.read_spi(FIFO_SIZE_1)?;
-        let len2 = self.read_spi(FIFO_SIZE_2)?;
-        let len3 = self.read_spi(FIFO_SIZE_3)?;
+    /// Turn the lens shading correction (LENC) of the DSP on or off.  Lens
+    /// correction brightens the corners of the image to reduce the vignetting
+    /// of the wide angle lenses fitted to most modules
+    pub fn set_lens_correction(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        update_bits(self, DSP_BANK, CTRL1, CTRL1_LENC, on)?;
+        self.configuration.lens_correction = on;
+        Ok(())
+    }
 
-        Ok(u32::from_be_bytes([0x00, len3, len2, len1]) as usize)
+    /// Bypass the DSP to read the raw Bayer output of the sensor, for debugging
+    /// the sensor independently of the image pipeline.  While bypassed none of
+    /// the image format, resolution or image settings apply to the readout
+    pub fn set_dsp_bypass(
+        &mut self, bypass: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        update_bits(self, DSP_BANK, R_BYPASS, R_BYPASS_DSP, bypass)
     }
 
-    /// Read the captured image into the provided buffer, returning the image
-    /// length in bytes
-    pub fn read_image(
-        &mut self, buffer: &mut [u8]
-    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
-        let image_size = self.image_size()?;
-        if buffer.len() < image_size {
-            return Err(OV2640Error::InvalidBufferSize)?;
-        }
+    /// Set the polarities of the parallel (DVP) output through COM10, to match
+    /// a DCMI host: whether data changes on the rising edge of PCLK and whether
+    /// HREF and VSYNC are active high.  The ArduChip FIFO does not depend on
+    /// these settings
+    pub fn set_sync_polarity(
+        &mut self, pclk_rising: bool, href_active_high: bool, vsync_active_high: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let com10 = sync_polarity_com10(0, pclk_rising, href_active_high, vsync_active_high);
+        modify_register(self, SENSOR_BANK, COM10, SYNC_POLARITY_COM10_MASK, com10)
+    }
 
-        if let Some(spi) = self.spi.as_mut() {
-            spi.write(&[FIFO_BURST]).map_err(OV2640Error::SpiError)?;
-            spi.transfer_in_place(buffer).map_err(OV2640Error::SpiError)?;
-            Ok(image_size)
-        } else {
-            Err(OV2640Error::NoSpiPeripheral)
+    /// Set the JPEG quality of the OV2640 Module through the quantization
+    /// scale (QS) register.  Higher quality gives larger images.  The encoder
+    /// only produces baseline JPEG, there is no register selecting progressive
+    /// output, so the quantization scale is the only control over the stream.
+    /// `rewrite_quantization_tables` can edit the tables of a captured JPEG
+    pub fn set_jpeg_quality(
+        &mut self, jpeg_quality: JpegQuality
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if self.configuration.image_format != ImageFormat::JPEG {
+            return Err(OV2640Error::CannotSetJpegQualityOnNonJPEG);
         }
+
+        self.write_registers(&jpeg_quality_registers(jpeg_quality))?;
+        self.configuration.jpeg_quality = jpeg_quality;
+        Ok(())
     }
 
-    /// Take the SPI Peripheral from the device
-    pub fn take_spi(&mut self) -> Option<SPI> {
-        self.spi.take()
+    /// Set the horizontal mirror and vertical flip of the OV2640 Module,
+    /// leaving the other bits of REG04 untouched
+    pub fn set_flip(
+        &mut self, flip: Flip
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        modify_register(self, SENSOR_BANK, REG04, FLIP_REG04_MASK, flip_reg04(0, flip))?;
+        self.configuration.flip = flip;
+        Ok(())
     }
 
-    /// Take the I2C Peripheral from the device
-    pub fn take_i2c(&mut self) -> Option<I2C> {
-        self.i2c.take()
+    /// Set the strobe (flash light) output of the OV2640 Module, used to fire
+    /// an external LED during the exposure of a frame.
+    ///
+    /// The strobe is controlled by the sensor bank COM22 register (0x4B), whose
+    /// bits are not documented in the datasheet.  `StrobeMode::Off` restores
+    /// the default value (0x20) and `StrobeMode::Custom` writes a value given
+    /// by the module vendor
+    pub fn set_strobe(
+        &mut self, strobe: StrobeMode
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&strobe_registers(strobe))?;
+        self.configuration.strobe = strobe;
+        Ok(())
     }
 
-    /// Write to an SPI register
-    fn write_spi(
-        &mut self, address: u8, value: u8
+    /// Set the banding filter of the OV2640 Module to remove flicker from
+    /// mains powered lighting.  Writes the band steps of the current
+    /// resolution to BD50 and BD60 and updates the frequency bits of COM3 and
+    /// the banding filter bit of COM8 in the sensor bank
+    pub fn set_banding_filter(
+        &mut self, banding_filter: BandingFilter
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        if let Some(spi) = self.spi.as_mut() {
-            spi.write(&[address | 0x80, value]).map_err(OV2640Error::SpiError)
-        } else {
-            Err(OV2640Error::NoSpiPeripheral)
-        }
+        self.write_registers(&band_step_registers(&self.configuration))?;
+        modify_register(self, SENSOR_BANK, COM3, COM3_BAND_MASK, com3_band_bits(banding_filter))?;
+        let com8 = self.read_register(COM8)?;
+        self.write_register(COM8, banding_filter_com8(com8, banding_filter))?;
+        self.configuration.banding_filter = banding_filter;
+        self.manual_banding_filter = manual_banding_filter(banding_filter);
+        Ok(())
     }
 
-    /// Read from an SPI register
-    fn read_spi(
-        &mut self, address: u8,
-    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
-        if let Some(spi) = self.spi.as_mut() {
-            let mut buffer = [address];
-            spi.transfer_in_place(&mut buffer).map_err(OV2640Error::SpiError)?;
-            Ok(buffer[0])
-        } else {
-            Err(OV2640Error::NoSpiPeripheral)
+    /// Turn the automatic detection of the mains frequency on or off through
+    /// the COM3 auto banding bit.  While on, the detected frequency overrides
+    /// the manual 50Hz / 60Hz selection, which takes effect again once it is
+    /// turned off.  Neither applies while the banding filter is disabled
+    pub fn set_auto_banding(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        update_bits(self, SENSOR_BANK, COM3, COM3_BAND_AUTO, on)?;
+        if self.configuration.banding_filter != BandingFilter::Disabled {
+            self.configuration.banding_filter = if on { BandingFilter::Auto } else { self.manual_banding_filter };
         }
+        Ok(())
     }
 
-    /// Write to a singular register via I2C
-    fn write_register(
-        &mut self, register: u8, value: u8
+    /// Replace the image with the internal color bar test pattern through the
+    /// COM7 color bar bit, to check the I2C / SPI wiring and capture pipeline
+    /// independently of the lens.  Turning it off restores normal output
+    pub fn set_test_pattern(
+        &mut self, on: bool
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        if let Some(i2c) = self.i2c.as_mut() {
-            i2c.write(I2C_ADDRESS, &[register, value])
-                .map_err(OV2640Error::I2CError)
-        } else {
-            Err(OV2640Error::NoI2cPeripheral)
+        update_bits(self, SENSOR_BANK, COM7, COM7_COLOR_BAR, on)
+    }
+
+    /// Turn night mode on or off.  In night mode the sensor inserts dummy
+    /// frames (COM1) so auto exposure can integrate for longer, trading frame
+    /// rate (down to an eighth) and motion blur for brighter low light frames
+    pub fn set_night_mode(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if on {
+            // Night mode relies on auto exposure to lengthen the integration
+            update_bits(self, SENSOR_BANK, COM8, COM8_AEC_EN, true)?;
         }
+        update_bits(self, SENSOR_BANK, COM1, COM1_NIGHT_MODE, on)
     }
 
-    /// Write to a set of registers via I2C
-    fn write_registers(
-        &mut self, registers: &[[u8; 2]]
+    /// Put the sensor into (or wake it from) soft standby through the COM2
+    /// standby bit.  Standby stops the sensor clocks, cutting the draw from
+    /// tens of milliamps while streaming to around a milliamp (the hardware
+    /// power down line goes lower still), and keeps the register contents, but
+    /// `init` should be called again after waking before the next capture
+    pub fn set_power_down(
+        &mut self, down: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        update_bits(self, SENSOR_BANK, COM2, COM2_STANDBY, down)
+    }
+
+    /// Set the exposure of the OV2640 Module.  Manual exposure turns off the
+    /// auto exposure and auto gain of COM8 and writes the exposure across the
+    /// AEC registers, `Exposure::Auto` turns them back on
+    pub fn set_exposure(
+        &mut self, exposure: Exposure
     ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
-        for register in registers {
-            self.write_register(register[0], register[1])?;
+        self.select_bank(SENSOR_BANK)?;
+        let com8 = self.read_register(COM8)?;
+        match exposure {
+            Exposure::Auto => self.write_register(COM8, com8 | COM8_AGC_EN | COM8_AEC_EN),
+            Exposure::Manual(value) => {
+                self.write_register(COM8, com8 & !(COM8_AGC_EN | COM8_AEC_EN))?;
+                self.write_register16(&EXPOSURE_REGISTERS, value)
+            },
         }
+    }
+
+    /// Read the current exposure of the OV2640 Module from the AEC registers
+    pub fn exposure(&mut self) -> Result<u16, OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK)?;
+        self.read_register16(&EXPOSURE_REGISTERS)
+    }
+
+    /// Set the ceiling of the automatic gain of the OV2640 Module, limiting the
+    /// noise amplified into dark frames
+    pub fn set_gain_ceiling(
+        &mut self, gain_ceiling: GainCeiling
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK)?;
+        let com9 = self.read_register(COM9)?;
+        self.write_register(COM9, gain_ceiling_com9(com9, gain_ceiling))?;
+        self.configuration.gain_ceiling = gain_ceiling;
         Ok(())
     }
 
-    /// Read the value from a register via I2C
-    fn read_register(
-        &mut self, register: u8
+    /// Set the clock divider (CLKRC) of the OV2640 Module, trading frame rate
+    /// for bandwidth on slow links.  The pixel clock is the input clock divided
+    /// by `clock_divider + 1`, only the low 6 bits are used
+    pub fn set_clock_divider(
+        &mut self, clock_divider: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(SENSOR_BANK)?;
+        self.write_register(CLKRC, clock_divider & 0x3F)?;
+        self.configuration.clock_divider = Some(clock_divider);
+        Ok(())
+    }
+
+    /// Crop the output to a `width` x `height` window of the sensor array
+    /// starting at (`x`, `y`).  The window must be a multiple of 4 pixels and
+    /// fit within the 1600x1200 sensor array
+    pub fn set_window(
+        &mut self, x: u16, y: u16, width: u16, height: u16
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(&window_registers(x, y, width, height)?)
+    }
+
+    /// Read back the DSP window (HSIZE/VSIZE) and output (OUTW/OUTH) sizes and
+    /// check that the output fits within the window.  Useful as a sanity check
+    /// after hand-tuning the DSP registers
+    pub fn validate_window_registers(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(DSP_BANK)?;
+        let window = [
+            self.read_register(HSIZE)?,
+            self.read_register(VSIZE)?,
+            self.read_register(VHYX)?,
+            self.read_register(TEST)?,
+        ];
+        let output = [self.read_register(ZMOW)?, self.read_register(ZMOH)?, self.read_register(ZMHH)?];
+
+        if !window_is_consistent(window, output) {
+            return Err(OV2640Error::InconsistentWindow);
+        }
+        Ok(())
+    }
+
+    /// Read a register from the given bank (DSP_BANK or SENSOR_BANK) of the
+    /// sensor.  The bank is left selected after the read, which does not affect
+    /// the driver as every setter selects its own bank first
+    pub fn read_sensor_register(
+        &mut self, bank: u8, register: u8
     ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
-        if let Some(i2c) = self.i2c.as_mut() {
-            let mut buffer = [0u8];
-            i2c.write_read(I2C_ADDRESS, &[register], &mut buffer)
-                .map_err(OV2640Error::I2CError)?;
-            Ok(buffer[0])
+        if !readable_registers(bank).contains(&register) {
+            return Err(OV2640Error::RegisterNotReadable);
+        }
+
+        self.select_bank(bank)?;
+        self.read_register(register)
+    }
+
+    /// Read every readable register of the given bank (DSP_BANK or SENSOR_BANK)
+    /// into `out` for diagnostics, `out[i]` holding the value of register
+    /// `readable_registers(bank)[i]`.  Returns the number of registers read
+    pub fn dump_registers(
+        &mut self, bank: u8, out: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let registers = readable_registers(bank);
+        if registers.is_empty() {
+            return Err(OV2640Error::RegisterNotReadable);
+        }
+        if out.len() < registers.len() {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        self.select_bank(bank)?;
+        for (value, register) in out.iter_mut().zip(registers) {
+            *value = self.read_register(*register)?;
+        }
+        Ok(registers.len())
+    }
+
+    /// Soft reset the sensor to its default register values by selecting the
+    /// sensor bank, setting the COM7 reset bit and waiting the reset delay.
+    /// Unlike `set_image_format` nothing is reconfigured, so `init` must be
+    /// called again afterwards.  Useful for recovering a sensor that has wedged
+    pub fn soft_reset(&mut self, delay: &mut dyn DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        SOFT_RESET_REGISTERS.write(self)?;
+        self.current_bank = None;
+        delay.delay_ms(self.reset_delay_ms);
+        Ok(())
+    }
+
+    /// Reset the sensor, using a hardware reset through the reset pin if one
+    /// was provided and a soft reset otherwise.  `init` must be called again
+    /// after resetting
+    pub fn reset(&mut self, delay: &mut dyn DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(reset) = self.reset.as_mut() {
+            reset.set_low().map_err(|err| OV2640Error::PinError(err.kind()))?;
+            delay.delay_ms(10);
+            reset.set_high().map_err(|err| OV2640Error::PinError(err.kind()))?;
+            self.current_bank = None;
+            delay.delay_ms(self.reset_delay_ms);
+            Ok(())
         } else {
-            Err(OV2640Error::NoI2cPeripheral)
+            self.soft_reset(delay)
+        }
+    }
+
+    /// Write a register in the given bank (DSP_BANK or SENSOR_BANK) of the
+    /// sensor for tuning not covered by the configuration.  The bank is left
+    /// selected after the write, which does not affect the driver as every
+    /// setter selects its own bank first
+    pub fn write_sensor_register(
+        &mut self, bank: u8, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.select_bank(bank)?;
+        self.write_register(register, value)
+    }
+
+    /// Write a register table, such as a vendor tuning list, to the OV2640
+    /// Module.  `[0xFF, bank]` entries switch between the DSP (0x00) and sensor
+    /// (0x01) banks and writing stops at a `[0xFF, 0xFF]` end marker
+    pub fn apply_register_table(
+        &mut self, table: &[[u8; 2]]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(table)
+    }
+
+    /// Put the sensor into standby by asserting its power down line, using the
+    /// power down pin if one was provided and the ArduChip GPIO otherwise
+    pub fn power_down(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(pwdn) = self.pwdn.as_mut() {
+            pwdn.set_high().map_err(|err| OV2640Error::PinError(err.kind()))
+        } else {
+            let gpio = self.read_spi(GPIO)?;
+            self.write_spi(GPIO, gpio | GPIO_PWDN_MASK)
+        }
+    }
+
+    /// Wake the sensor from standby by releasing its power down line
+    pub fn power_up(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(pwdn) = self.pwdn.as_mut() {
+            pwdn.set_low().map_err(|err| OV2640Error::PinError(err.kind()))
+        } else {
+            let gpio = self.read_spi(GPIO)?;
+            self.write_spi(GPIO, gpio & !GPIO_PWDN_MASK)
+        }
+    }
+
+    /// Flush the OV2640's FIFO
+    pub fn flush_fifo(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK)?;
+        self.capture_state = CaptureState::Idle;
+        Ok(())
+    }
+
+    /// Abort a capture in progress, clearing the FIFO and resetting its read
+    /// pointer so the next capture starts from a clean state
+    pub fn abort_capture(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK)?;
+        self.write_spi(FIFO, FIFO_RDPTR_RST_MASK)?;
+        self.capture_state = CaptureState::Idle;
+        Ok(())
+    }
+
+    /// Start capturing into the FIFO, returning a handle that can be polled
+    /// for the capture to complete
+    pub fn start_capture(
+        &mut self
+    ) -> Result<CaptureInProgress<'_, I2C, SPI, PWDN, RST>, OV2640Error<I2CErr, SPIErr>> {
+        self.write_spi(FIFO, FIFO_CLEAR_MASK)?;
+        self.write_spi(FIFO, FIFO_START_MASK)?;
+        self.capture_state = CaptureState::Capturing;
+        Ok(CaptureInProgress { driver: self })
+    }
+
+    /// Check whether the capture is complete
+    pub fn is_capture_done(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        let done = self.read_spi(TRIGGER)? & CAPTURE_COMPLETE_MASK != 0;
+        if done && self.capture_state == CaptureState::Capturing {
+            self.capture_state = CaptureState::Ready;
+        }
+        Ok(done)
+    }
+
+    /// Read and decode the ArduChip trigger register, a richer alternative to
+    /// `is_capture_done`.  Reading the status does not advance the capture
+    /// state, `is_capture_done` must still be polled before reading an image
+    pub fn status(&mut self) -> Result<Status, OV2640Error<I2CErr, SPIErr>> {
+        Ok(Status::from_bits(self.read_spi(TRIGGER)?))
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// until `timeout_ms` milliseconds have passed
+    pub fn capture(
+        &mut self, delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.capture_with_poll_interval(delay, timeout_ms, CAPTURE_POLL_INTERVAL_MS)
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// every `poll_interval_ms` milliseconds (at least 1) until `timeout_ms`
+    /// milliseconds have passed
+    pub fn capture_with_poll_interval(
+        &mut self, delay: &mut dyn DelayNs, timeout_ms: u32, poll_interval_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        let poll_interval_ms = poll_interval_ms.max(1);
+        self.start_capture()?;
+
+        let mut elapsed = 0;
+        while !self.is_capture_done()? {
+            if elapsed >= timeout_ms {
+                return Err(OV2640Error::CaptureTimeout);
+            }
+            delay.delay_ms(poll_interval_ms);
+            elapsed = elapsed.saturating_add(poll_interval_ms);
+        }
+        Ok(())
+    }
+
+    /// Start a capture and return without waiting for it to complete, for use
+    /// with an external interrupt on the capture done line.  Once the interrupt
+    /// fires read the image with `collect_after_capture` (`is_capture_done` can
+    /// still be used to confirm the capture is complete)
+    pub fn capture_nonblocking_start(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.start_capture().map(|_| ())
+    }
+
+    /// Read the image of a capture started with `capture_nonblocking_start`
+    /// into the provided buffer, returning the image length in bytes.  Fails
+    /// with `CaptureNotReady` if the capture has not completed
+    pub fn collect_after_capture(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.is_capture_done()?;
+        self.read_image(buffer)
+    }
+
+    /// Get the length of the image in the FIFO
+    ///
+    /// The length is spread over three registers that are read in separate
+    /// transactions, so a read that straddles a frame boundary can combine
+    /// bytes from two different lengths.  The length is re-read until two
+    /// consecutive reads agree (or the retry limit is reached)
+    ///
+    /// Fails with `InvalidFifoLength` for an empty or oversized FIFO.  The
+    /// length is returned as a `u32` since the FIFO can hold more bytes than a
+    /// 16-bit `usize` can address
+    pub fn image_size(&mut self) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+        let mut size = self.read_fifo_length()?;
+        for _ in 0..FIFO_SIZE_READ_ATTEMPTS {
+            let next = self.read_fifo_length()?;
+            if next == size {
+                break;
+            }
+            size = next;
+        }
+        check_fifo_length(size)
+    }
+
+    /// Get the approximate compression ratio of the captured image, the size
+    /// of the uncompressed frame divided by the image length in the FIFO.
+    /// Useful for tuning `JpegQuality` against the available bandwidth
+    pub fn last_compression_ratio(&mut self) -> Result<f32, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.image_size()?;
+        Ok(compression_ratio(&self.configuration, image_size))
+    }
+
+    /// Read the captured image into the provided buffer, returning the image
+    /// length in bytes.  Fails with `CaptureNotReady` unless `is_capture_done`
+    /// has reported a capture started since the last read as complete
+    pub fn read_image(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let fifo_length = self.ready_image_size()?;
+        let image_size = image_length(&self.configuration, fifo_length);
+        if buffer.len() < image_size {
+            return Err(OV2640Error::InvalidBufferSize)?;
+        }
+
+        self.read_fifo(&mut buffer[..image_size])?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into the provided buffer without the dummy byte
+    /// some ArduCam boards clock out before the image in a burst read,
+    /// returning the image length in bytes.  The dummy byte is detected from
+    /// the JPEG start of image marker, uncompressed images are returned as read
+    pub fn read_image_exact(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.read_image(buffer)?;
+        Ok(strip_dummy_byte(self.configuration.image_format, &mut buffer[..image_size]))
+    }
+
+    /// Read the captured JPEG into the provided buffer and check it starts with
+    /// the start of image marker (0xFFD8) and ends with the end of image marker
+    /// (0xFFD9), failing with `CorruptJpeg` otherwise.  Any FIFO padding after
+    /// the last end of image marker is left out of the returned length
+    pub fn read_jpeg(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.read_image_exact(buffer)?;
+        jpeg_length(&buffer[..image_size]).ok_or(OV2640Error::CorruptJpeg)
+    }
+
+    /// Read the captured image into the provided buffer, returning it as a
+    /// `Frame` with the format and dimensions of the current configuration.
+    /// The frame data leaves out the dummy byte of some ArduCam boards and any
+    /// FIFO padding after the end of a JPEG
+    pub fn read_frame<'a>(
+        &mut self, buffer: &'a mut [u8]
+    ) -> Result<Frame<'a>, OV2640Error<I2CErr, SPIErr>> {
+        let mut image_size = self.read_image_exact(buffer)?;
+        if self.configuration.image_format == ImageFormat::JPEG {
+            image_size = jpeg_length(&buffer[..image_size]).unwrap_or(image_size);
+        }
+
+        let (width, height) = self.configuration.frame_dimensions();
+        Ok(Frame {
+            format: self.configuration.image_format,
+            width,
+            height,
+            data: &buffer[..image_size],
+        })
+    }
+
+    /// Read the captured image into a `heapless::Vec` holding up to `N` bytes,
+    /// failing with `InvalidBufferSize` if the image is larger
+    #[cfg(feature = "heapless")]
+    pub fn read_image_vec<const N: usize>(
+        &mut self
+    ) -> Result<heapless::Vec<u8, N>, OV2640Error<I2CErr, SPIErr>> {
+        let fifo_length = self.ready_image_size()?;
+        let image_size = image_length(&self.configuration, fifo_length);
+        let mut image = heapless::Vec::new();
+        image.resize(image_size, 0).map_err(|_| OV2640Error::InvalidBufferSize)?;
+
+        self.read_fifo(&mut image)?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image)
+    }
+
+    /// Capture an image and read it into the provided buffer, returning the
+    /// image length in bytes.  Fails with `CaptureTimeout` if the capture does
+    /// not complete within `timeout_ms` milliseconds
+    pub fn snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.capture(delay, timeout_ms)?;
+        self.read_image(buffer)
+    }
+
+    /// Capture an image and read it into the provided buffer like `snapshot`,
+    /// stepping the resolution down and retrying whenever the capture times
+    /// out.  Resolutions are tried from the configured resolution down through
+    /// `Resolution` in declaration order, failing with `CaptureTimeout` once the
+    /// smallest times out (or straight away for QVGA, whose resolution is
+    /// fixed).  The resolution that succeeded stays configured and is returned
+    /// with the image length
+    pub fn snapshot_with_fallback(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<(usize, Resolution), OV2640Error<I2CErr, SPIErr>> {
+        loop {
+            match self.snapshot(buffer, delay, timeout_ms) {
+                Err(OV2640Error::CaptureTimeout) => {},
+                result => return result.map(|image_size| (image_size, self.configuration.resolution)),
+            }
+            if self.configuration.image_format == ImageFormat::QVGA {
+                return Err(OV2640Error::CaptureTimeout);
+            }
+            let resolution = lower_resolution(self.configuration.resolution)
+                .ok_or(OV2640Error::CaptureTimeout)?;
+            self.set_resolution(resolution)?;
+        }
+    }
+
+    /// Capture an image and read it into the provided buffer like `snapshot`,
+    /// timing the capture and readout with `now_ms`, a free running millisecond
+    /// clock.  Returns the image length in bytes and the elapsed milliseconds
+    pub fn timed_snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32,
+        mut now_ms: impl FnMut() -> u32
+    ) -> Result<(usize, u32), OV2640Error<I2CErr, SPIErr>> {
+        let start = now_ms();
+        let image_size = self.snapshot(buffer, delay, timeout_ms)?;
+        Ok((image_size, now_ms().wrapping_sub(start)))
+    }
+
+    /// Capture and read the next frame of a stream into the provided buffer,
+    /// returning the image length in bytes.  The sensor is not reconfigured, the
+    /// FIFO is flushed before each capture so no stale data from the previous
+    /// frame is read.  Fails with `CaptureTimeout` if the capture does not
+    /// complete within `timeout_ms` milliseconds
+    pub fn stream_next(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.flush_fifo()?;
+        self.capture(delay, timeout_ms)?;
+        self.read_image_exact(buffer)
+    }
+
+    /// Read the captured image through `chunk`, passing each filled part of it
+    /// to `sink` until the whole image has been read, so large images can be
+    /// streamed out without a buffer for the whole image.  Returns the image
+    /// length in bytes
+    pub fn read_image_chunked(
+        &mut self, chunk: &mut [u8], mut sink: impl FnMut(&[u8])
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if chunk.is_empty() {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let image_size = self.ready_image_size()?;
+        let mut remaining = image_size;
+        while remaining > 0 {
+            let length = remaining.min(chunk.len());
+            self.read_fifo(&mut chunk[..length])?;
+            sink(&chunk[..length]);
+            remaining -= length;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read `rows` scanlines starting at `start_row` of a captured uncompressed
+    /// image into the provided buffer, returning the band length in bytes.  The
+    /// FIFO can only be read in order, so the scanlines before the band are
+    /// read (through the buffer) and discarded.  Fails with `InvalidScanlines`
+    /// for JPEG images or scanlines outside the frame
+    pub fn read_scanlines(
+        &mut self, start_row: u16, rows: u16, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let (offset, length) = scanline_band(&self.configuration, start_row, rows)
+            .ok_or(OV2640Error::InvalidScanlines)?;
+        if buffer.len() < length {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+        if offset + length > self.ready_image_size()? {
+            return Err(OV2640Error::InvalidScanlines);
+        }
+
+        self.write_spi(FIFO, FIFO_RDPTR_RST_MASK)?;
+        let mut skipped = 0;
+        while skipped < offset {
+            let skip = (offset - skipped).min(length);
+            self.read_fifo(&mut buffer[..skip])?;
+            skipped += skip;
+        }
+        self.read_fifo(&mut buffer[..length])?;
+        Ok(length)
+    }
+
+    /// Read the captured JPEG into the provided buffer, stopping once the JPEG
+    /// end of image marker (0xFFD9) has been read.  The FIFO can hold padding
+    /// after the end of the JPEG that strict decoders reject, so the returned
+    /// length (including the marker) may be shorter than `image_size`
+    pub fn read_jpeg_trimmed(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let image_size = self.ready_image_size()?;
+        if buffer.len() < image_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let mut read = 0;
+        while read < image_size {
+            let end = (read + JPEG_SCAN_CHUNK_SIZE).min(image_size);
+            self.read_fifo(&mut buffer[read..end])?;
+
+            // Scan from the last byte of the previous chunk in case the marker
+            // is split between two chunks
+            if let Some(jpeg_end) = jpeg_end(&buffer[..end], read.saturating_sub(1)) {
+                self.capture_state = CaptureState::Idle;
+                return Ok(jpeg_end);
+            }
+            read = end;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into a buffer for DMA backed SPI transfers that
+    /// require aligned buffers and transfer lengths (e.g. 4 bytes on the STM32
+    /// and ESP32 DMA engines).  The buffer must start on an `alignment` byte
+    /// boundary and hold the image length rounded up to a multiple of
+    /// `alignment`, the bytes after the image are padding.  Returns the image
+    /// length in bytes
+    pub fn read_image_aligned(
+        &mut self, buffer: &mut [u8], alignment: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if alignment == 0 || !(buffer.as_ptr() as usize).is_multiple_of(alignment) {
+            return Err(OV2640Error::MisalignedBuffer);
+        }
+
+        let image_size = self.ready_image_size()?;
+        let transfer_size = image_size.next_multiple_of(alignment);
+        if buffer.len() < transfer_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        self.read_fifo(&mut buffer[..transfer_size])?;
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into the provided buffer in `block`-sized SPI
+    /// transfers for DMA engines that need fixed transfer lengths, returning
+    /// the image length in bytes.  The final block is padded with whatever the
+    /// FIFO clocks out after the image, so the buffer must hold the image
+    /// length rounded up to a multiple of `block`
+    pub fn read_image_blocks(
+        &mut self, buffer: &mut [u8], block: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if block == 0 {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        let image_size = self.ready_image_size()?;
+        let transfer_size = image_size.next_multiple_of(block);
+        if buffer.len() < transfer_size {
+            return Err(OV2640Error::InvalidBufferSize);
+        }
+
+        for chunk in buffer[..transfer_size].chunks_mut(block) {
+            self.read_fifo(chunk)?;
+        }
+        self.capture_state = CaptureState::Idle;
+        Ok(image_size)
+    }
+
+    /// Read the captured image into the provided buffer like `read_image`,
+    /// measuring the readout throughput with `now_us`, a free running
+    /// microsecond clock.  The result is available from `last_read_throughput`
+    pub fn read_image_timed(
+        &mut self, buffer: &mut [u8], mut now_us: impl FnMut() -> u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        let start = now_us();
+        let image_size = self.read_image(buffer)?;
+        let elapsed = now_us().wrapping_sub(start);
+
+        self.last_read_throughput = read_throughput(image_size, elapsed);
+        Ok(image_size)
+    }
+
+    /// Get the throughput (in bytes per second) of the last `read_image_timed`
+    pub fn last_read_throughput(&self) -> Option<u32> {
+        self.last_read_throughput
+    }
+
+    /// Get the largest image in bytes the current configuration can produce,
+    /// for sizing the image buffer
+    pub fn max_image_size(&self) -> usize {
+        self.configuration.max_image_size()
+    }
+
+    /// Take the SPI Peripheral from the device
+    pub fn take_spi(&mut self) -> Option<SPI> {
+        self.spi.take()
+    }
+
+    /// Take the I2C Peripheral from the device
+    pub fn take_i2c(&mut self) -> Option<I2C> {
+        self.current_bank = None;
+        self.i2c.take()
+    }
+
+    /// Take the Power Down Pin from the device
+    pub fn take_power_pin(&mut self) -> Option<PWDN> {
+        self.pwdn.take()
+    }
+
+    /// Take the Reset Pin from the device
+    pub fn take_reset_pin(&mut self) -> Option<RST> {
+        self.reset.take()
+    }
+
+    /// Reset the sensor and write the register tables for an image format
+    fn write_image_format(
+        &mut self, image_format: ImageFormat, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.soft_reset(delay)?;
+
+        for table in image_format_tables(image_format) {
+            self.write_registers(table)?;
+        }
+        self.manual_banding_filter = BandingFilter::Hz50;
+        // The format tables overwrite the byte order and lens correction bits
+        if self.configuration.byte_swap {
+            self.set_byte_swap(true)?;
+        }
+        if self.configuration.lens_correction {
+            self.set_lens_correction(true)?;
+        }
+        Ok(())
+    }
+
+    /// Write the register table for a resolution
+    fn write_resolution(
+        &mut self, resolution: Resolution
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_registers(resolution_registers(resolution))
+    }
+
+    /// Get the length of the image in the FIFO as a buffer length, failing with
+    /// `CaptureNotReady` unless a capture has completed since the last read and
+    /// with `InvalidFifoLength` for a length above `max_read_bytes`
+    fn ready_image_size(&mut self) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        if self.capture_state != CaptureState::Ready {
+            return Err(OV2640Error::CaptureNotReady);
+        }
+        let image_size = buffer_length(self.image_size()?)?;
+        if self.max_read_bytes.is_some_and(|max_read_bytes| image_size > max_read_bytes) {
+            return Err(OV2640Error::InvalidFifoLength);
+        }
+        Ok(image_size)
+    }
+
+    /// Read the length of the image in the FIFO from the FIFO size registers
+    fn read_fifo_length(&mut self) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+        let len1 = self.read_spi(FIFO_SIZE_1)?;
+        let len2 = self.read_spi(FIFO_SIZE_2)?;
+        let len3 = self.read_spi(FIFO_SIZE_3)?;
+
+        Ok(fifo_length(len1, len2, len3))
+    }
+
+    /// Burst read from the FIFO into the buffer.  The FIFO read pointer is kept
+    /// between bursts, so consecutive calls continue where the last stopped
+    fn read_fifo(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            spi.transaction(&mut [
+                Operation::Write(&[FIFO_BURST]),
+                Operation::Read(buffer),
+            ]).map_err(OV2640Error::SpiError)
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Write to an SPI register
+    fn write_spi(
+        &mut self, address: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            spi.write(&[address | 0x80, value]).map_err(OV2640Error::SpiError)
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Read from an SPI register
+    fn read_spi(
+        &mut self, address: u8,
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        if let Some(spi) = self.spi.as_mut() {
+            // The value is clocked out while the byte after the address is sent
+            let mut buffer = [address & 0x7F, 0x00];
+            spi.transfer_in_place(&mut buffer).map_err(OV2640Error::SpiError)?;
+            Ok(buffer[1])
+        } else {
+            Err(OV2640Error::NoSpiPeripheral)
+        }
+    }
+
+    /// Write a value to a register via I2C, tracking writes to the bank select
+    /// register so redundant bank selects are skipped
+    fn write_register(
+        &mut self, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if register == 0xFF {
+            return self.select_bank(value);
+        }
+        self.write_i2c(register, value)
+    }
+
+    /// Select a register bank, skipping the write if the bank is already
+    /// selected
+    fn select_bank(&mut self, bank: u8) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if self.current_bank == Some(bank) {
+            return Ok(());
         }
+        // The bank is unknown if the write fails part way
+        self.current_bank = None;
+        self.write_i2c(0xFF, bank)?;
+        self.current_bank = Some(bank);
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Write a value to a register via I2C
+    fn write_i2c(
+        &mut self, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        if let Some(i2c) = self.i2c.as_mut() {
+            i2c.write(self.i2c_address, &[register, value])
+                .map_err(OV2640Error::I2CError)
+        } else {
+            Err(OV2640Error::NoI2cPeripheral)
+        }
+    }
+
+    /// Write to a set of registers via I2C
+    fn write_registers(
+        &mut self, registers: &[[u8; 2]]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.write_many(registers)
+    }
+
+    /// Read the value from a register via I2C
+    fn read_register(
+        &mut self, register: u8
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        if let Some(i2c) = self.i2c.as_mut() {
+            let mut buffer = [0u8];
+            i2c.write_read(self.i2c_address, &[register], &mut buffer)
+                .map_err(OV2640Error::I2CError)?;
+            Ok(buffer[0])
+        } else {
+            Err(OV2640Error::NoI2cPeripheral)
+        }
+    }
+}
+
+impl<I2C, SPI, PWDN, RST, I2CErr, SPIErr> RegisterAccess for OV2640<I2C, SPI, PWDN, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin,
+    RST: OutputPin {
+    type Error = OV2640Error<I2CErr, SPIErr>;
+
+    fn read(&mut self, register: u8) -> Result<u8, Self::Error> {
+        self.read_register(register)
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.write_register(register, value)
+    }
+}
+
+impl<I2C, SPI, PWDN, RST, I2CErr, SPIErr> FrameReader for OV2640<I2C, SPI, PWDN, RST> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr>,
+    PWDN: OutputPin,
+    RST: OutputPin {
+    type Error = OV2640Error<I2CErr, SPIErr>;
+
+    fn start_frame(&mut self) -> Result<(), Self::Error> {
+        self.start_capture().map(|_| ())
+    }
+
+    fn frame_done(&mut self) -> Result<bool, Self::Error> {
+        self.is_capture_done()
+    }
+
+    fn read_frame(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_image(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, NoopDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::digital::{
+        Mock as PinMock, State as PinState, Transaction as PinTransaction,
+    };
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    type Camera = OV2640<I2cMock, SpiMock<u8>>;
+
+    fn i2c_write(register: u8, value: u8) -> I2cTransaction {
+        I2cTransaction::write(I2C_ADDRESS, vec![register, value])
+    }
+
+    fn i2c_read(register: u8, value: u8) -> I2cTransaction {
+        I2cTransaction::write_read(I2C_ADDRESS, vec![register], vec![value])
+    }
+
+    fn spi_write(address: u8, value: u8) -> [SpiTransaction<u8>; 3] {
+        [
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![address | 0x80, value]),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    fn spi_read(address: u8, value: u8) -> [SpiTransaction<u8>; 3] {
+        [
+            SpiTransaction::transaction_start(),
+            SpiTransaction::transfer_in_place(vec![address & 0x7F, 0x00], vec![0x00, value]),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    fn fifo_length_reads(length: u32) -> Vec<SpiTransaction<u8>> {
+        let [len1, len2, len3, _] = length.to_le_bytes();
+        [spi_read(FIFO_SIZE_1, len1), spi_read(FIFO_SIZE_2, len2), spi_read(FIFO_SIZE_3, len3)]
+            .concat()
+    }
+
+    /// Start of a capture followed by `pending` polls finding it not done
+    fn pending_capture(pending: usize) -> Vec<SpiTransaction<u8>> {
+        let mut spi = [spi_write(FIFO, FIFO_CLEAR_MASK), spi_write(FIFO, FIFO_START_MASK)].concat();
+        for _ in 0..pending {
+            spi.extend(spi_read(TRIGGER, 0x00));
+        }
+        spi
+    }
+
+    /// Capture completing on the first poll followed by the FIFO length read
+    /// (twice, as the length is re-read until it is stable) by an image read
+    fn completed_capture(length: u32) -> Vec<SpiTransaction<u8>> {
+        [
+            pending_capture(0),
+            spi_read(TRIGGER, CAPTURE_COMPLETE_MASK).to_vec(),
+            fifo_length_reads(length),
+            fifo_length_reads(length),
+        ].concat()
+    }
+
+    fn fifo_burst(data: &[u8]) -> [SpiTransaction<u8>; 4] {
+        [
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![FIFO_BURST]),
+            SpiTransaction::read_vec(data.to_vec()),
+            SpiTransaction::transaction_end(),
+        ]
+    }
+
+    /// Fake sensor on the I2C bus keeping the registers of each bank, so read
+    /// modify write sequences can be checked by their effect.  Every register
+    /// write other than a bank select is recorded with its bank
+    #[derive(Default)]
+    struct FakeSensor {
+        // Bank selected by the last write to 0xFF
+        bank: u8,
+        // Register addressed by the last write, read by the next read
+        address: u8,
+        // Register values by (bank, register), unwritten registers read as 0
+        registers: HashMap<(u8, u8), u8>,
+        // (bank, register, value) of each register write in order
+        writes: Vec<(u8, u8, u8)>,
+        // Number of writes to 0xFF
+        bank_selects: usize,
+        // Number of writes to 0xFF selecting the bank already selected
+        redundant_bank_selects: usize,
+        // Number of I2C transactions
+        transactions: usize,
+        // Number of upcoming transactions to fail with a NAK
+        naks: usize,
+    }
+
+    impl FakeSensor {
+        fn with_register(mut self, bank: u8, register: u8, value: u8) -> Self {
+            self.registers.insert((bank, register), value);
+            self
+        }
+
+        fn register(&self, bank: u8, register: u8) -> u8 {
+            self.registers.get(&(bank, register)).copied().unwrap_or(0)
+        }
+    }
+
+    impl embedded_hal::i2c::ErrorType for FakeSensor {
+        type Error = embedded_hal::i2c::ErrorKind;
+    }
+
+    impl I2c for FakeSensor {
+        fn transaction(
+            &mut self, address: u8, operations: &mut [embedded_hal::i2c::Operation<'_>]
+        ) -> Result<(), Self::Error> {
+            assert_eq!(address, I2C_ADDRESS);
+            self.transactions += 1;
+            if self.naks > 0 {
+                self.naks -= 1;
+                return Err(embedded_hal::i2c::ErrorKind::NoAcknowledge(
+                    embedded_hal::i2c::NoAcknowledgeSource::Address
+                ));
+            }
+            for operation in operations {
+                match operation {
+                    embedded_hal::i2c::Operation::Write(&[0xFF, bank]) => {
+                        if bank == self.bank {
+                            self.redundant_bank_selects += 1;
+                        }
+                        self.bank = bank;
+                        self.bank_selects += 1;
+                    },
+                    embedded_hal::i2c::Operation::Write(&[register, value]) => {
+                        self.registers.insert((self.bank, register), value);
+                        self.writes.push((self.bank, register, value));
+                    },
+                    embedded_hal::i2c::Operation::Write(&[register]) => self.address = register,
+                    embedded_hal::i2c::Operation::Read(buffer) => {
+                        buffer.fill(self.register(self.bank, self.address));
+                    },
+                    operation => panic!("unexpected i2c operation {:?}", operation),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Register writes of tables written one after the other from an unknown
+    /// bank, as (bank, register, value) like `FakeSensor::writes`
+    fn table_writes(tables: &[&[[u8; 2]]]) -> Vec<(u8, u8, u8)> {
+        let mut bank = 0;
+        let mut writes = Vec::new();
+        for table in tables {
+            for &[register, value] in table.iter().take_while(|entry| **entry != [0xFF, 0xFF]) {
+                if register == 0xFF {
+                    bank = value;
+                } else {
+                    writes.push((bank, register, value));
+                }
+            }
+        }
+        writes
+    }
+
+    /// Build a camera over mocks expecting the given transactions, returning
+    /// handles to the mocks to check with `done`
+    fn camera(
+        i2c: &[I2cTransaction], spi: &[SpiTransaction<u8>]
+    ) -> (Camera, I2cMock, SpiMock<u8>) {
+        let i2c = I2cMock::new(i2c);
+        let spi = SpiMock::new(spi);
+        (OV2640::new(Some(i2c.clone()), Some(spi.clone())), i2c, spi)
+    }
+
+    #[test]
+    fn image_size_rereads_until_two_reads_agree() {
+        let spi = [fifo_length_reads(0x1000), fifo_length_reads(0x1200), fifo_length_reads(0x1200)]
+            .concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        assert_eq!(camera.image_size().unwrap(), 0x1200);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn reset_waits_the_configured_delay_after_releasing_the_pin() {
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&[]);
+        let mut reset = PinMock::new(&[
+            PinTransaction::set(PinState::Low),
+            PinTransaction::set(PinState::High),
+        ]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(10),
+            DelayTransaction::delay_ms(250),
+        ]);
+        let mut camera = OV2640::new_with_reset_pin(
+            Some(i2c.clone()), Some(spi.clone()), reset.clone()
+        );
+        camera.set_reset_delay_ms(250);
+
+        camera.reset(&mut delay).unwrap();
+        reset.done();
+        delay.done();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_image_format_writes_the_rgb565_tables() {
+        let configuration = ConfigurationBuilder::new().resolution(Resolution::R320x240).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+
+        camera.set_image_format(ImageFormat::RGB565, &mut NoopDelay::new()).unwrap();
+        assert_eq!(camera.configuration().image_format, ImageFormat::RGB565);
+        assert_eq!(sensor.writes, table_writes(&[
+            &SOFT_RESET_REGISTERS, &JPEG_INIT_REGISTER, &RGB565_REGISTERS, &JPEG_320x240_REGISTERS,
+        ]));
+    }
+
+    #[test]
+    fn sensor_register_access_selects_the_bank_first() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_write(COM9, 0x48),
+            i2c_write(0xFF, DSP_BANK),
+            i2c_read(IMAGE_MODE, 0x10),
+        ], &[]);
+
+        camera.write_sensor_register(SENSOR_BANK, COM9, 0x48).unwrap();
+        assert_eq!(camera.read_sensor_register(DSP_BANK, IMAGE_MODE).unwrap(), 0x10);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn capture_polls_until_the_capture_completes() {
+        let spi = [&pending_capture(3)[..], &spi_read(TRIGGER, CAPTURE_COMPLETE_MASK)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut delay = CheckedDelay::new(&vec![DelayTransaction::delay_ms(1); 3]);
+
+        camera.capture(&mut delay, 100).unwrap();
+        i2c.done();
+        spi.done();
+        delay.done();
+    }
+
+    #[test]
+    fn capture_times_out_when_the_capture_never_completes() {
+        let (mut camera, mut i2c, mut spi) = camera(&[], &pending_capture(4));
+        let mut delay = CheckedDelay::new(&vec![DelayTransaction::delay_ms(1); 3]);
+
+        assert!(matches!(camera.capture(&mut delay, 3), Err(OV2640Error::CaptureTimeout)));
+        i2c.done();
+        spi.done();
+        delay.done();
+    }
+
+    #[test]
+    fn set_flip_toggles_the_vertical_flip_without_clobbering_reg04() {
+        // Mirrored, with an unrelated bit of REG04 set
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, REG04, REG04_HFLIP | 0x08);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.set_flip(Flip { horizontal: true, vertical: true }).unwrap();
+        camera.set_flip(Flip { horizontal: true, vertical: false }).unwrap();
+        assert_eq!(sensor.writes, [
+            (SENSOR_BANK, REG04, REG04_HFLIP | REG04_VFLIP | REG04_VREF_EN | 0x08),
+            (SENSOR_BANK, REG04, REG04_HFLIP | 0x08),
+        ]);
+    }
+
+    #[test]
+    fn set_jpeg_quality_writes_qs_only_for_jpeg() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_jpeg_quality(JpegQuality::High).unwrap();
+        assert_eq!(sensor.writes, [(DSP_BANK, QS, 0x04)]);
+
+        let configuration = ConfigurationBuilder::new().qvga().build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+        assert!(matches!(
+            camera.set_jpeg_quality(JpegQuality::High),
+            Err(OV2640Error::CannotSetJpegQualityOnNonJPEG)
+        ));
+        assert_eq!(camera.configuration().jpeg_quality, JpegQuality::Medium);
+        assert!(sensor.writes.is_empty());
+    }
+
+    #[test]
+    fn read_image_chunked_reassembles_the_image() {
+        let image: Vec<u8> = (0..10).collect();
+        let spi = [
+            &completed_capture(10)[..],
+            &fifo_burst(&image[0..4]),
+            &fifo_burst(&image[4..8]),
+            // The last chunk is only as long as the rest of the image
+            &fifo_burst(&image[8..10]),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        camera.capture(&mut NoopDelay::new(), 0).unwrap();
+        let mut read = Vec::new();
+        let length = camera.read_image_chunked(&mut [0; 4], |chunk| read.extend_from_slice(chunk)).unwrap();
+        assert_eq!(length, 10);
+        assert_eq!(read, image);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn banding_filter_selects_the_mains_frequency_in_com3() {
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM3, COM3_DEFAULT);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_banding_filter(BandingFilter::Hz50).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM3), COM3_DEFAULT | COM3_BAND_50HZ);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_banding_filter(BandingFilter::Hz60).unwrap();
+        // Only the frequency bits of COM3 change
+        assert_eq!(sensor.register(SENSOR_BANK, COM3), COM3_DEFAULT);
+        // The band steps of both frequencies are always written
+        assert_eq!(sensor.register(SENSOR_BANK, BD50), 0xBB);
+        assert_eq!(sensor.register(SENSOR_BANK, BD60), 0x9C);
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), COM8_BNDF_EN);
+    }
+
+    #[test]
+    fn banding_filter_writes_the_band_steps_of_the_resolution() {
+        let configuration = ConfigurationBuilder::new().resolution(Resolution::R320x240).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+        camera.set_banding_filter(BandingFilter::Hz60).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, BD50), 0xCA);
+        assert_eq!(sensor.register(SENSOR_BANK, BD60), 0xA8);
+    }
+
+    #[test]
+    fn init_leaves_the_table_banding_filter_by_default() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.init(&mut NoopDelay::new()).unwrap();
+        // COM3 and COM8 are only written by the image format tables
+        assert_eq!(sensor.register(SENSOR_BANK, COM3), COM3_DEFAULT | COM3_BAND_50HZ);
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), 0xE5);
+    }
+
+    #[test]
+    fn set_window_writes_a_centered_crop() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.set_window(400, 300, 800, 600).unwrap();
+        // The window registers read back as a window the output fits in
+        camera.validate_window_registers().unwrap();
+        // Sizes are in units of 4 pixels, bit 8 of the offsets is in VHYX
+        for (register, value) in [
+            (HSIZE, 200), (VSIZE, 150), (XOFFL, 0x90), (YOFFL, 0x2C), (VHYX, 0x11),
+            (TEST, 0x00), (ZMOW, 200), (ZMOH, 150), (ZMHH, 0x00),
+        ] {
+            assert_eq!(sensor.register(DSP_BANK, register), value, "register {:#04x}", register);
+        }
+    }
+
+    #[test]
+    fn set_window_rejects_a_window_outside_the_sensor() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        assert!(matches!(camera.set_window(1000, 0, 800, 600), Err(OV2640Error::InvalidWindow)));
+        assert!(sensor.writes.is_empty());
+    }
+
+    #[test]
+    fn set_image_format_writes_the_grayscale_tables() {
+        let configuration = ConfigurationBuilder::new().resolution(Resolution::R320x240).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+
+        camera.set_image_format(ImageFormat::Grayscale, &mut NoopDelay::new()).unwrap();
+        assert_eq!(sensor.writes, table_writes(&[
+            &SOFT_RESET_REGISTERS, &JPEG_INIT_REGISTER, &YUV422_REGISTERS, &GRAYSCALE_REGISTERS,
+            &JPEG_320x240_REGISTERS,
+        ]));
+    }
+
+    #[test]
+    fn register_tables_take_one_transaction_per_write() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.apply_register_table(&[
+            [0xFF, DSP_BANK], [IMAGE_MODE, 0x10], [0xFF, DSP_BANK], [QS, 0x0C],
+            [0xFF, SENSOR_BANK], [COM9, 0x48], [0xFF, 0xFF], [COM8, 0x00],
+        ]).unwrap();
+        // The redundant bank select and the entries after the end marker are
+        // not sent
+        assert_eq!(sensor.transactions, 5);
+        assert_eq!(sensor.bank_selects, 2);
+    }
+
+    #[test]
+    fn set_sharpness_writes_the_manual_level_or_the_auto_bit() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, DSP_BANK),
+            i2c_write(SHARPNESS_ADDR, 0x01),
+            i2c_write(SHARPNESS_DATA, SHARPNESS_MANUAL | 5),
+            i2c_write(SHARPNESS_ADDR, 0x01),
+            i2c_write(SHARPNESS_DATA, SHARPNESS_AUTO),
+        ], &[]);
+
+        camera.set_sharpness(Sharpness::Manual(5)).unwrap();
+        camera.set_sharpness(Sharpness::Auto).unwrap();
+        assert!(matches!(
+            camera.set_sharpness(Sharpness::Manual(MAX_SHARPNESS + 1)),
+            Err(OV2640Error::LevelOutOfRange)
+        ));
+        assert_eq!(camera.configuration().sharpness, Sharpness::Auto);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_white_balance_writes_manual_gains_and_restores_auto() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_white_balance(WhiteBalance::Manual { r_gain: 0x5E, g_gain: 0x41, b_gain: 0x54 }).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, AWB_CTRL), AWB_CTRL_MANUAL);
+        assert_eq!(sensor.register(DSP_BANK, AWB_R_GAIN), 0x5E);
+        assert_eq!(sensor.register(DSP_BANK, AWB_G_GAIN), 0x41);
+        assert_eq!(sensor.register(DSP_BANK, AWB_B_GAIN), 0x54);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_white_balance(WhiteBalance::Auto).unwrap();
+        assert_eq!(camera.configuration().white_balance, WhiteBalance::Auto);
+        assert_eq!(sensor.register(DSP_BANK, AWB_CTRL) & AWB_CTRL_MANUAL, 0);
+    }
+
+    #[test]
+    fn soft_reset_sets_the_com7_reset_bit_and_waits() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_write(COM7, 0x80),
+            // The bank is selected again as the reset leaves it unknown
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_write(CLKRC, 0x01),
+        ], &[]);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_ms(RESET_DELAY_MS)]);
+
+        camera.soft_reset(&mut delay).unwrap();
+        camera.set_clock_divider(0x01).unwrap();
+        i2c.done();
+        spi.done();
+        delay.done();
+    }
+
+    #[test]
+    fn image_size_rejects_empty_and_all_ones_lengths() {
+        for length in [0x000000, 0xFFFFFF] {
+            let spi = [fifo_length_reads(length), fifo_length_reads(length)].concat();
+            let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+            assert!(matches!(camera.image_size(), Err(OV2640Error::InvalidFifoLength)));
+            i2c.done();
+            spi.done();
+        }
+    }
+
+    #[test]
+    fn collect_after_capture_reads_once_the_capture_completes() {
+        let image = [0xFF, 0xD8, 0x00, 0xFF, 0xD9];
+        let spi = [
+            &pending_capture(1)[..],
+            &spi_read(TRIGGER, CAPTURE_COMPLETE_MASK),
+            &fifo_length_reads(5),
+            &fifo_length_reads(5),
+            &fifo_burst(&image),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 8];
+
+        camera.capture_nonblocking_start().unwrap();
+        // Collecting before the interrupt fires finds the capture not done
+        assert!(matches!(camera.collect_after_capture(&mut buffer), Err(OV2640Error::CaptureNotReady)));
+        assert_eq!(camera.collect_after_capture(&mut buffer).unwrap(), 5);
+        assert_eq!(buffer[..5], image);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_gamma_curve_writes_each_point_through_the_data_port() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.set_gamma_curve(&LOW_LIGHT_GAMMA_CURVE).unwrap();
+        // Writing the address rewinds to the first point, which each data
+        // write then advances
+        let mut expected = vec![(DSP_BANK, GAMMA_ADDR, 0x00)];
+        expected.extend(LOW_LIGHT_GAMMA_CURVE.iter().map(|&point| (DSP_BANK, GAMMA_DATA, point)));
+        assert_eq!(sensor.writes, expected);
+    }
+
+    #[test]
+    fn set_test_pattern_sets_and_clears_the_color_bar_bit() {
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM7, 0x40);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.set_test_pattern(true).unwrap();
+        camera.set_test_pattern(false).unwrap();
+        assert_eq!(sensor.writes, [
+            (SENSOR_BANK, COM7, 0x40 | COM7_COLOR_BAR),
+            (SENSOR_BANK, COM7, 0x40),
+        ]);
+    }
+
+    #[test]
+    fn image_size_masks_the_high_fifo_size_byte() {
+        // The largest legal length with the undocumented top bit of
+        // FIFO_SIZE_3 set
+        let length = 0x80_0000 | MAX_FIFO_SIZE as u32;
+        let spi = [fifo_length_reads(length), fifo_length_reads(length)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        assert_eq!(camera.image_size().unwrap(), MAX_FIFO_SIZE as u32);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_night_mode_toggles_the_com1_dummy_frame_bits() {
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM1, 0x0A);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_night_mode(true).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM1), 0x0A | COM1_NIGHT_MODE);
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), COM8_AEC_EN);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_night_mode(false).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM1), 0x0A);
+        // Auto exposure is left on
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), COM8_AEC_EN);
+    }
+
+    #[test]
+    fn set_exposure_splits_a_manual_exposure_across_the_aec_registers() {
+        let mut sensor = FakeSensor::default()
+            .with_register(SENSOR_BANK, COM8, 0xC7)
+            .with_register(SENSOR_BANK, REG04, 0xA8)
+            .with_register(SENSOR_BANK, REG45, 0xC0);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_exposure(Exposure::Manual(0xABCD)).unwrap();
+        assert_eq!(camera.exposure().unwrap(), 0xABCD);
+        // AEC[1:0], AEC[9:2] and AEC[15:10], keeping the other bits
+        assert_eq!(sensor.register(SENSOR_BANK, REG04), 0xA8 | 0x01);
+        assert_eq!(sensor.register(SENSOR_BANK, AEC), 0xF3);
+        assert_eq!(sensor.register(SENSOR_BANK, REG45), 0xC0 | 0x2A);
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), 0xC7 & !(COM8_AGC_EN | COM8_AEC_EN));
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_exposure(Exposure::Auto).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), 0xC7);
+    }
+
+    #[test]
+    fn apply_register_table_switches_banks_on_0xff_entries() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.apply_register_table(&[
+            [0xFF, SENSOR_BANK], [COM9, 0x48], [0xFF, DSP_BANK], [QS, 0x10],
+            [0xFF, SENSOR_BANK], [CLKRC, 0x01],
+        ]).unwrap();
+        assert_eq!(sensor.writes, [
+            (SENSOR_BANK, COM9, 0x48), (DSP_BANK, QS, 0x10), (SENSOR_BANK, CLKRC, 0x01),
+        ]);
+    }
+
+    #[test]
+    fn snapshot_captures_and_reads_the_image() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        let spi = [&completed_capture(6)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        assert_eq!(camera.snapshot(&mut buffer, &mut NoopDelay::new(), 100).unwrap(), 6);
+        assert_eq!(buffer[..6], image);
+        // The image can only be read once per capture
+        assert!(matches!(camera.read_image(&mut buffer), Err(OV2640Error::CaptureNotReady)));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn flush_and_start_capture_write_different_fifo_bits() {
+        let spi = [
+            spi_write(FIFO, 0x01),
+            spi_write(FIFO, 0x01),
+            spi_write(FIFO, 0x02),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        camera.flush_fifo().unwrap();
+        camera.start_capture().unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_image_exact_drops_a_leading_dummy_byte() {
+        let image = [0x00, 0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        let spi = [&completed_capture(7)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert_eq!(camera.read_image_exact(&mut buffer).unwrap(), 6);
+        assert_eq!(buffer[..6], image[1..]);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn drivers_address_the_sensor_at_their_own_i2c_address() {
+        for address in [0x30, 0x31] {
+            let mut i2c = I2cMock::new(&[
+                I2cTransaction::write(address, vec![0xFF, SENSOR_BANK]),
+                I2cTransaction::write(address, vec![CLKRC, 0x01]),
+            ]);
+            let mut camera = OV2640::<_, SpiMock<u8>>::new_with_address(
+                Some(i2c.clone()), None, address
+            );
+
+            camera.set_clock_divider(0x01).unwrap();
+            i2c.done();
+        }
+    }
+
+    #[test]
+    fn verify_configuration_detects_a_dropped_write() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.init(&mut NoopDelay::new()).unwrap();
+        assert!(camera.verify_configuration().unwrap());
+
+        let zmow = sensor.register(DSP_BANK, ZMOW);
+        sensor.registers.insert((DSP_BANK, ZMOW), !zmow);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        assert!(!camera.verify_configuration().unwrap());
+    }
+
+    #[test]
+    fn init_writes_the_configured_clock_divider_to_clkrc() {
+        let mut sensor = FakeSensor::default();
+        let configuration = ConfigurationBuilder::default().clock_divider(0x43).build();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(
+            configuration, Some(&mut sensor), None
+        );
+        camera.init(&mut NoopDelay::new()).unwrap();
+        // Only the low six bits of CLKRC hold the divider
+        assert_eq!(sensor.register(SENSOR_BANK, CLKRC), 0x03);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_clock_divider(0x07).unwrap();
+        assert_eq!(camera.configuration().clock_divider, Some(0x07));
+        assert_eq!(sensor.register(SENSOR_BANK, CLKRC), 0x07);
+    }
+
+    #[test]
+    fn init_leaves_the_dsp_bank_selected() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.init(&mut NoopDelay::new()).unwrap();
+        assert_eq!(camera.current_bank, Some(DSP_BANK));
+        assert_eq!(sensor.bank, DSP_BANK);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn read_image_vec_reads_the_image_into_a_vec() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        let spi = [&completed_capture(6)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        let vec = camera.read_image_vec::<8192>().unwrap();
+        assert_eq!(vec[..], image);
+        i2c.done();
+        spi.done();
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn read_image_vec_rejects_an_image_larger_than_the_vec() {
+        let (mut camera, mut i2c, mut spi) = camera(&[], &completed_capture(6));
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert!(matches!(camera.read_image_vec::<4>(), Err(OV2640Error::InvalidBufferSize)));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn incandescent_preset_and_raw_white_balance_write_the_awb_gains() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_light_mode(LightMode::Incandescent).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, AWB_CTRL), AWB_CTRL_MANUAL);
+        assert_eq!(sensor.register(DSP_BANK, AWB_R_GAIN), 0x3C);
+        assert_eq!(sensor.register(DSP_BANK, AWB_G_GAIN), 0x40);
+        assert_eq!(sensor.register(DSP_BANK, AWB_B_GAIN), 0x78);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_white_balance_raw([0x12, 0x34, 0x56]).unwrap();
+        assert_eq!(
+            camera.configuration().white_balance,
+            WhiteBalance::Manual { r_gain: 0x12, g_gain: 0x34, b_gain: 0x56 }
+        );
+        assert_eq!(sensor.register(DSP_BANK, 0xCC), 0x12);
+        assert_eq!(sensor.register(DSP_BANK, 0xCD), 0x34);
+        assert_eq!(sensor.register(DSP_BANK, 0xCE), 0x56);
+    }
+
+    #[test]
+    fn read_image_reads_a_length_beyond_u16() {
+        let length = 0x1_2345;
+        let image = vec![0x5A; length];
+        let spi = [&completed_capture(length as u32)[..], &fifo_burst(&image)].concat();
+        let configuration = ConfigurationBuilder::default().image_format(ImageFormat::RGB565).build();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640::with_configuration(
+            configuration, Some(i2c.clone()), Some(spi.clone())
+        );
+        let mut buffer = vec![0; length];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert_eq!(camera.read_image(&mut buffer).unwrap(), length);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn capture_in_progress_polls_to_the_image_length() {
+        let spi = [
+            pending_capture(2),
+            spi_read(TRIGGER, CAPTURE_COMPLETE_MASK).to_vec(),
+            fifo_length_reads(6),
+            fifo_length_reads(6),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        let mut capture = camera.start_capture().unwrap();
+        assert_eq!(capture.poll().unwrap(), None);
+        assert_eq!(capture.poll().unwrap(), None);
+        assert_eq!(capture.poll().unwrap(), Some(6));
+        assert!(camera.capture_state == CaptureState::Ready);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn product_id_reads_the_chip_id_registers() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_read(CHIP_ID_HIGH, 0x42),
+            i2c_read(CHIP_ID_LOW, 0x26),
+            i2c_read(CHIP_ID_HIGH, 0x42),
+            i2c_read(CHIP_ID_LOW, 0x26),
+        ], &[]);
+
+        assert_eq!(camera.product_id().unwrap(), (0x42, 0x26));
+        assert!(camera.spi_connected().unwrap());
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn capture_with_poll_interval_delays_between_polls() {
+        let spi = [&pending_capture(3)[..], &spi_read(TRIGGER, CAPTURE_COMPLETE_MASK)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut delay = CheckedDelay::new(&vec![DelayTransaction::delay_ms(5); 3]);
+
+        camera.capture_with_poll_interval(&mut delay, 100, 5).unwrap();
+        i2c.done();
+        spi.done();
+        delay.done();
+    }
+
+    #[test]
+    fn capture_with_poll_interval_times_out_in_whole_intervals() {
+        // 20ms at a 5ms interval is 4 delays and 5 polls
+        let (mut camera, mut i2c, mut spi) = camera(&[], &pending_capture(5));
+        let mut delay = CheckedDelay::new(&vec![DelayTransaction::delay_ms(5); 4]);
+
+        assert!(matches!(
+            camera.capture_with_poll_interval(&mut delay, 20, 5),
+            Err(OV2640Error::CaptureTimeout)
+        ));
+        i2c.done();
+        spi.done();
+        delay.done();
+    }
+
+    #[test]
+    fn set_power_down_toggles_the_com2_standby_bit() {
+        // COM2 defaults to 2x output drive
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM2, 0x02);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_power_down(true).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM2), 0x02 | COM2_STANDBY);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_power_down(false).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM2), 0x02);
+    }
+
+    #[test]
+    fn set_byte_swap_toggles_the_image_mode_byte_swap_bit() {
+        // RGB565 output in IMAGE_MODE
+        let mut sensor = FakeSensor::default().with_register(DSP_BANK, IMAGE_MODE, 0x08);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_byte_swap(true).unwrap();
+        assert!(camera.configuration().byte_swap);
+        assert_eq!(sensor.register(DSP_BANK, IMAGE_MODE), 0x08 | IMAGE_MODE_BYTE_SWAP);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_byte_swap(false).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, IMAGE_MODE), 0x08);
+    }
+
+    #[test]
+    fn frame_reader_frames_spi_register_accesses() {
+        let spi = [
+            // Writes set the top address bit and send the value after it
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0x84, 0x01]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0x84, 0x02]),
+            SpiTransaction::transaction_end(),
+            // Reads clear it and clock the value out on a second byte
+            SpiTransaction::transaction_start(),
+            SpiTransaction::transfer_in_place(vec![0x41, 0x00], vec![0x00, 0x08]),
+            SpiTransaction::transaction_end(),
+        ];
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        camera.start_frame().unwrap();
+        assert!(camera.frame_done().unwrap());
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn configuration_reflects_the_last_setters() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_saturation(Saturation::Saturation4).unwrap();
+        camera.set_brightness(Brightness::Brightness0).unwrap();
+        camera.set_brightness(Brightness::Brightness3).unwrap();
+        camera.set_contrast(Contrast::Contrast1).unwrap();
+
+        assert_eq!(camera.configuration().saturation, Saturation::Saturation4);
+        assert_eq!(camera.configuration().brightness, Brightness::Brightness3);
+        assert_eq!(camera.configuration().contrast, Contrast::Contrast1);
+    }
+
+    #[test]
+    fn read_jpeg_trims_fifo_padding_after_the_end_of_image() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9, 0x00, 0x00];
+        let spi = [&completed_capture(8)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert_eq!(camera.read_jpeg(&mut buffer).unwrap(), 6);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_jpeg_rejects_a_jpeg_without_an_end_of_image() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0x56, 0x78];
+        let spi = [&completed_capture(6)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert!(matches!(camera.read_jpeg(&mut buffer), Err(OV2640Error::CorruptJpeg)));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_image_format_writes_yuv422_without_the_jpeg_enable() {
+        let configuration = ConfigurationBuilder::new().resolution(Resolution::R320x240).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+
+        camera.set_image_format(ImageFormat::YUV422, &mut NoopDelay::new()).unwrap();
+        assert_eq!(camera.configuration().max_image_size(), 320 * 240 * 2);
+        assert_eq!(sensor.writes, table_writes(&[
+            &SOFT_RESET_REGISTERS, &JPEG_INIT_REGISTER, &YUV422_OUTPUT_REGISTERS,
+            &JPEG_320x240_REGISTERS,
+        ]));
+        // IMAGE_MODE selects YUV422 with the JPEG output enable (0x10) clear
+        assert_eq!(sensor.register(DSP_BANK, IMAGE_MODE), 0x00);
+    }
+
+    #[test]
+    fn init_with_retries_recovers_from_naks() {
+        let mut sensor = FakeSensor { naks: 2, ..FakeSensor::default() };
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.init_with_retries(&mut NoopDelay::new(), 2).unwrap();
+        assert_eq!(sensor.naks, 0);
+
+        let mut sensor = FakeSensor { naks: 2, ..FakeSensor::default() };
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        assert!(matches!(
+            camera.init_with_retries(&mut NoopDelay::new(), 1),
+            Err(OV2640Error::I2CError(embedded_hal::i2c::ErrorKind::NoAcknowledge(_)))
+        ));
+    }
+
+    #[test]
+    fn read_scanlines_skips_to_a_mid_image_band() {
+        // Two 320 byte rows skipped, then rows 2 and 3 read
+        let row = |row: u8| vec![row; 160 * 2];
+        let skipped = [row(0), row(1)].concat();
+        let band = [row(2), row(3)].concat();
+        let spi = [
+            &completed_capture(160 * 120 * 2)[..],
+            &spi_write(FIFO, FIFO_RDPTR_RST_MASK),
+            &fifo_burst(&skipped),
+            &fifo_burst(&band),
+        ].concat();
+        let configuration = ConfigurationBuilder::default()
+            .image_format(ImageFormat::RGB565)
+            .resolution(Resolution::R160x120)
+            .build();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640::with_configuration(
+            configuration, Some(i2c.clone()), Some(spi.clone())
+        );
+        let mut buffer = [0; 1024];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert_eq!(camera.read_scanlines(2, 2, &mut buffer).unwrap(), band.len());
+        assert_eq!(buffer[..band.len()], band);
+        assert!(matches!(
+            camera.read_scanlines(119, 2, &mut buffer),
+            Err(OV2640Error::InvalidScanlines)
+        ));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn timed_snapshot_reports_the_elapsed_clock_time() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        let spi = [&completed_capture(6)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+        // The clock wraps around during the snapshot
+        let mut clock = [u32::MAX - 4, 10].into_iter();
+
+        let result = camera.timed_snapshot(
+            &mut buffer, &mut NoopDelay::new(), 100, || clock.next().unwrap()
+        );
+        assert_eq!(result.unwrap(), (6, 15));
+        assert_eq!(clock.next(), None);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_lens_correction_toggles_the_ctrl1_lenc_bit() {
+        let mut sensor = FakeSensor::default().with_register(DSP_BANK, CTRL1, 0xFD);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_lens_correction(true).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, CTRL1), 0xFF);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_lens_correction(false).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, CTRL1), 0xFD);
+
+        // The bit survives the image format tables of init
+        let configuration = ConfigurationBuilder::default().lens_correction(true).build();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(
+            configuration, Some(&mut sensor), None
+        );
+        camera.init(&mut NoopDelay::new()).unwrap();
+        assert_eq!(sensor.register(DSP_BANK, CTRL1) & CTRL1_LENC, CTRL1_LENC);
+    }
+
+    #[test]
+    fn set_dsp_bypass_toggles_the_r_bypass_bit() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_dsp_bypass(true).unwrap();
+        assert_eq!(sensor.writes.last(), Some(&(DSP_BANK, R_BYPASS, R_BYPASS_DSP)));
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_dsp_bypass(false).unwrap();
+        assert_eq!(sensor.writes.last(), Some(&(DSP_BANK, R_BYPASS, 0x00)));
+    }
+
+    #[test]
+    fn read_image_rejects_reads_before_the_capture_is_done() {
+        let (mut camera, mut i2c, mut spi) = camera(&[], &pending_capture(1));
+        let mut buffer = [0; 16];
+
+        // Never started
+        assert!(matches!(camera.read_image(&mut buffer), Err(OV2640Error::CaptureNotReady)));
+        // Started but not yet done
+        camera.start_capture().unwrap();
+        assert!(!camera.is_capture_done().unwrap());
+        assert!(matches!(camera.read_image(&mut buffer), Err(OV2640Error::CaptureNotReady)));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_frame_describes_a_jpeg_capture() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9, 0x00, 0x00];
+        let spi = [&completed_capture(8)[..], &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        let frame = camera.read_frame(&mut buffer).unwrap();
+        assert_eq!(frame, Frame { format: ImageFormat::JPEG, width: 1024, height: 768, data: &image[..6] });
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_sync_polarity_writes_the_com10_polarity_bits() {
+        let combinations = [
+            ((true, true, true), COM10_PCLK_RISING),
+            ((false, false, false), COM10_HREF_NEGATIVE | COM10_VSYNC_NEGATIVE),
+            ((true, false, true), COM10_PCLK_RISING | COM10_HREF_NEGATIVE),
+        ];
+        for ((pclk_rising, href_active_high, vsync_active_high), bits) in combinations {
+            // The other COM10 bits are kept
+            let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM10, 0xE5);
+            let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+            camera.set_sync_polarity(pclk_rising, href_active_high, vsync_active_high).unwrap();
+            assert_eq!(sensor.register(SENSOR_BANK, COM10), (0xE5 & !0x1A) | bits);
+        }
+    }
+
+    #[test]
+    fn try_new_needs_at_least_one_peripheral() {
+        assert!(matches!(Camera::try_new(None, None), Err(OV2640Error::NoPeripherals)));
+
+        let mut i2c = I2cMock::new(&[]);
+        let camera = Camera::try_new(Some(i2c.clone()), None).unwrap();
+        assert!(camera.has_i2c() && !camera.has_spi());
+        i2c.done();
+    }
+
+    #[test]
+    fn dump_registers_reads_each_readable_register_of_the_bank() {
+        let registers = readable_registers(SENSOR_BANK);
+        let mut sensor = FakeSensor::default();
+        for &register in registers {
+            sensor = sensor.with_register(SENSOR_BANK, register, register ^ 0x5A);
+        }
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        let mut out = [0; 64];
+
+        assert_eq!(camera.dump_registers(SENSOR_BANK, &mut out).unwrap(), registers.len());
+        for (value, register) in out.iter().zip(registers) {
+            assert_eq!(*value, register ^ 0x5A, "register {:#04x}", register);
+        }
+        assert!(matches!(camera.dump_registers(0x02, &mut out), Err(OV2640Error::RegisterNotReadable)));
+        assert!(matches!(
+            camera.dump_registers(SENSOR_BANK, &mut out[..8]),
+            Err(OV2640Error::InvalidBufferSize)
+        ));
+    }
+
+    #[test]
+    fn init_only_selects_a_bank_when_it_changes() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.init(&mut NoopDelay::new()).unwrap();
+        // Each bank select of init switches the bank
+        assert_eq!(sensor.bank_selects, 14);
+        assert_eq!(sensor.redundant_bank_selects, 0);
+    }
+
+    #[test]
+    fn failed_bank_select_forgets_the_current_bank() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, DSP_BANK),
+            i2c_write(QS, 0x04),
+            i2c_write(0xFF, SENSOR_BANK).with_error(embedded_hal::i2c::ErrorKind::Other),
+            // The failed write may have selected either bank
+            i2c_write(0xFF, DSP_BANK),
+            i2c_write(QS, 0x04),
+        ], &[]);
+
+        camera.set_jpeg_quality(JpegQuality::High).unwrap();
+        assert!(matches!(
+            camera.set_clock_divider(0x01),
+            Err(OV2640Error::I2CError(embedded_hal::i2c::ErrorKind::Other))
+        ));
+        assert_eq!(camera.current_bank, None);
+        camera.set_jpeg_quality(JpegQuality::High).unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn stream_next_flushes_and_captures_each_frame() {
+        let first = [0xFF, 0xD8, 0x01, 0xFF, 0xD9];
+        let second = [0xFF, 0xD8, 0x02, 0x03, 0xFF, 0xD9];
+        let spi = [
+            &spi_write(FIFO, FIFO_CLEAR_MASK)[..],
+            &completed_capture(5),
+            &fifo_burst(&first),
+            &spi_write(FIFO, FIFO_CLEAR_MASK),
+            &completed_capture(6),
+            &fifo_burst(&second),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        assert_eq!(camera.stream_next(&mut buffer, &mut NoopDelay::new(), 100).unwrap(), 5);
+        assert_eq!(buffer[..5], first);
+        assert_eq!(camera.stream_next(&mut buffer, &mut NoopDelay::new(), 100).unwrap(), 6);
+        assert_eq!(buffer[..6], second);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_image_rejects_a_length_above_max_read_bytes() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        let spi = [&completed_capture(6)[..], &fifo_length_reads(6), &fifo_length_reads(6), &fifo_burst(&image)].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.set_max_read_bytes(Some(5));
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        assert!(matches!(camera.read_image(&mut buffer), Err(OV2640Error::InvalidFifoLength)));
+        // Nothing was read, so the image can still be read once the cap allows
+        camera.set_max_read_bytes(Some(6));
+        assert_eq!(camera.read_image(&mut buffer).unwrap(), 6);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_special_effect_raw_writes_the_custom_u_and_v() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, DSP_BANK),
+            i2c_write(0x7C, 0x00),
+            i2c_write(0x7D, 0x18),
+            i2c_write(0x7C, 0x05),
+            i2c_write(0x7D, 0x5C),
+            i2c_write(0x7D, 0x93),
+        ], &[]);
+
+        camera.set_special_effect_raw(0x5C, 0x93).unwrap();
+        assert_eq!(camera.configuration().special_effect, SpecialEffect::Normal);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn average_luminance_reads_yavg_from_the_sensor_bank() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_read(YAVG, 0x7B),
+        ], &[]);
+
+        assert_eq!(camera.average_luminance().unwrap(), 0x7B);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn abort_capture_clears_the_fifo_and_returns_to_idle() {
+        let spi = [
+            &pending_capture(1)[..],
+            &spi_write(FIFO, FIFO_CLEAR_MASK),
+            &spi_write(FIFO, FIFO_RDPTR_RST_MASK),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+        let mut buffer = [0; 16];
+
+        camera.start_capture().unwrap();
+        assert!(!camera.is_capture_done().unwrap());
+        camera.abort_capture().unwrap();
+        assert!(camera.capture_state == CaptureState::Idle);
+        assert!(matches!(camera.read_image(&mut buffer), Err(OV2640Error::CaptureNotReady)));
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn read_image_blocks_pads_the_final_block() {
+        let image = [0xFF, 0xD8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xFF, 0xD9];
+        let spi = [
+            &completed_capture(10)[..],
+            &fifo_length_reads(10),
+            &fifo_length_reads(10),
+            &fifo_burst(&image[..4]),
+            &fifo_burst(&image[4..8]),
+            // Two bytes of padding clocked out after the image
+            &fifo_burst(&[0xFF, 0xD9, 0x00, 0x00]),
+        ].concat();
+        let (mut camera, mut i2c, mut spi) = camera(&[], &spi);
+
+        camera.capture(&mut NoopDelay::new(), 100).unwrap();
+        // The image rounded up to whole blocks does not fit
+        assert!(matches!(
+            camera.read_image_blocks(&mut [0; 10], 4),
+            Err(OV2640Error::InvalidBufferSize)
+        ));
+        let mut buffer = [0; 12];
+        assert_eq!(camera.read_image_blocks(&mut buffer, 4).unwrap(), 10);
+        assert_eq!(buffer[..10], image);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn soft_reset_waits_the_configured_reset_delay() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_ms(250)]);
+
+        camera.set_reset_delay_ms(250);
+        camera.soft_reset(&mut delay).unwrap();
+        delay.done();
+    }
+
+    #[test]
+    fn modify_register_applies_the_mask_over_i2c() {
+        let (mut camera, mut i2c, mut spi) = camera(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_read(COM10, 0xE5),
+            i2c_write(COM10, 0xEA),
+            // The bank is still selected for the next modify
+            i2c_read(COM10, 0xEA),
+            i2c_write(COM10, 0x6A),
+        ], &[]);
+
+        modify_register(&mut camera, SENSOR_BANK, COM10, 0x0F, 0x0A).unwrap();
+        update_bits(&mut camera, SENSOR_BANK, COM10, 0x80, false).unwrap();
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn last_compression_ratio_divides_the_frame_size_by_the_fifo_length() {
+        // A 320x240 frame is 153600 bytes uncompressed
+        let spi = [fifo_length_reads(15_360), fifo_length_reads(15_360)].concat();
+        let configuration = ConfigurationBuilder::default().resolution(Resolution::R320x240).build();
+        let mut i2c = I2cMock::new(&[]);
+        let mut spi = SpiMock::new(&spi);
+        let mut camera = OV2640::with_configuration(
+            configuration, Some(i2c.clone()), Some(spi.clone())
+        );
+
+        assert_eq!(camera.last_compression_ratio().unwrap(), 10.0);
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn snapshot_with_fallback_steps_down_after_a_timeout() {
+        let image = [0xFF, 0xD8, 0x12, 0x34, 0xFF, 0xD9];
+        // With no timeout the first poll finding the capture pending times out
+        let spi = [&pending_capture(1)[..], &completed_capture(6), &fifo_burst(&image)].concat();
+        let mut spi = SpiMock::new(&spi);
+        let configuration = ConfigurationBuilder::default().resolution(Resolution::R1600x1200).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::with_configuration(configuration, Some(&mut sensor), Some(spi.clone()));
+        let mut buffer = [0; 16];
+
+        let result = camera.snapshot_with_fallback(&mut buffer, &mut NoopDelay::new(), 0);
+        assert_eq!(result.unwrap(), (6, Resolution::R1280x1024));
+        assert_eq!(camera.configuration().resolution, Resolution::R1280x1024);
+        assert_eq!(buffer[..6], image);
+        assert_eq!(sensor.writes, table_writes(&[&JPEG_1280x1024_REGISTERS]));
+        spi.done();
+    }
+
+    #[test]
+    fn snapshot_with_fallback_times_out_for_qvga() {
+        let mut spi = SpiMock::new(&pending_capture(1));
+        let configuration = ConfigurationBuilder::new().qvga().build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::with_configuration(configuration, Some(&mut sensor), Some(spi.clone()));
+        let mut buffer = [0; 16];
+
+        assert!(matches!(
+            camera.snapshot_with_fallback(&mut buffer, &mut NoopDelay::new(), 0),
+            Err(OV2640Error::CaptureTimeout)
+        ));
+        assert!(sensor.writes.is_empty());
+        spi.done();
+    }
+
+    #[test]
+    fn apply_register_table_writes_a_typed_table() {
+        const TUNING: RegisterTable<5> = RegisterTable::new([
+            [0xFF, SENSOR_BANK],
+            [CLKRC, 0x01],
+            [0xFF, DSP_BANK],
+            [QS, 0x0C],
+            [0xFF, 0xFF],
+        ]);
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+
+        camera.apply_register_table(&TUNING).unwrap();
+        assert_eq!(sensor.writes, [(SENSOR_BANK, CLKRC, 0x01), (DSP_BANK, QS, 0x0C)]);
+    }
+
+    #[test]
+    fn has_i2c_and_has_spi_report_the_peripherals_given() {
+        let i2c = || Some(I2cMock::new(&[]));
+        let spi = || Some(SpiMock::new(&[]));
+        let combinations: [(Camera, bool, bool); 4] = [
+            (OV2640::new(i2c(), spi()), true, true),
+            (OV2640::new(i2c(), None), true, false),
+            (OV2640::new(None, spi()), false, true),
+            (OV2640::new(None, None), false, false),
+        ];
+        for (mut camera, has_i2c, has_spi) in combinations {
+            assert_eq!((camera.has_i2c(), camera.has_spi()), (has_i2c, has_spi));
+            if let Some(mut i2c) = camera.take_i2c() {
+                i2c.done();
+            }
+            if let Some(mut spi) = camera.take_spi() {
+                spi.done();
+            }
+        }
+    }
+
+    #[test]
+    fn probe_reports_each_interface_separately() {
+        // SPI answers but the sensor does not acknowledge
+        let (mut camera, mut i2c, mut spi) = camera(
+            &[i2c_write(0xFF, SENSOR_BANK).with_error(embedded_hal::i2c::ErrorKind::Other)],
+            &[spi_write(TEST_REGISTER, 0x52), spi_read(TEST_REGISTER, 0x52)].concat(),
+        );
+        assert_eq!(camera.probe().unwrap(), ModuleInfo { i2c_ok: false, spi_ok: true, chip_id: None });
+        i2c.done();
+        spi.done();
+
+        // The sensor answers but there is no SPI peripheral
+        let mut i2c = I2cMock::new(&[
+            i2c_write(0xFF, SENSOR_BANK),
+            i2c_read(CHIP_ID_HIGH, 0x42),
+            i2c_read(CHIP_ID_LOW, 0x26),
+        ]);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(i2c.clone()), None);
+        assert_eq!(camera.probe().unwrap(), ModuleInfo { i2c_ok: true, spi_ok: false, chip_id: Some(0x4226) });
+        i2c.done();
+    }
+
+    #[test]
+    fn auto_banding_sets_the_com3_auto_detect_bit() {
+        let mut sensor = FakeSensor::default().with_register(SENSOR_BANK, COM3, COM3_DEFAULT);
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_banding_filter(BandingFilter::Auto).unwrap();
+        assert_eq!(sensor.register(SENSOR_BANK, COM3), COM3_DEFAULT | COM3_BAND_AUTO);
+        assert_eq!(sensor.register(SENSOR_BANK, COM8), COM8_BNDF_EN);
+
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        camera.set_banding_filter(BandingFilter::Hz50).unwrap();
+        camera.set_auto_banding(true).unwrap();
+        assert_eq!(camera.configuration().banding_filter, BandingFilter::Auto);
+        // Turning auto detection off falls back to the manual selection
+        camera.set_auto_banding(false).unwrap();
+        assert_eq!(camera.configuration().banding_filter, BandingFilter::Hz50);
+        assert_eq!(sensor.register(SENSOR_BANK, COM3), COM3_DEFAULT | COM3_BAND_50HZ);
+
+        // The manual frequency is restored without reading COM3 back
+        let configuration = ConfigurationBuilder::new().banding_filter(BandingFilter::Hz60).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+        camera.set_auto_banding(true).unwrap();
+        camera.set_auto_banding(false).unwrap();
+        assert_eq!(camera.configuration().banding_filter, BandingFilter::Hz60);
+        assert_eq!(sensor.transactions, 5);
+    }
+
+    #[test]
+    fn status_decodes_a_composite_trigger_byte() {
+        let (mut camera, mut i2c, mut spi) = camera(
+            &[],
+            &[spi_read(TRIGGER, VSYNC_MASK | SHUTTER_MASK | CAPTURE_COMPLETE_MASK | 0x80), spi_read(TRIGGER, SHUTTER_MASK)].concat(),
+        );
+        assert_eq!(camera.status().unwrap(), Status { vsync: true, shutter: true, capture_done: true });
+        assert_eq!(camera.status().unwrap(), Status { vsync: false, shutter: true, capture_done: false });
+        i2c.done();
+        spi.done();
+    }
+
+    #[test]
+    fn set_resolution_is_not_configurable_for_qvga() {
+        let configuration = ConfigurationBuilder::new().image_format(ImageFormat::QVGA).build();
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::with_configuration(configuration, Some(&mut sensor), None);
+        assert!(matches!(
+            camera.set_resolution(Resolution::R640x480),
+            Err(OV2640Error::ResolutionNotConfigurableForFormat)
+        ));
+        assert_eq!(sensor.transactions, 0);
+    }
+
+    #[test]
+    fn set_for_display_scales_the_dsp_output_to_the_display() {
+        let mut sensor = FakeSensor::default();
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        let chosen = camera.set_for_display(160, 128, &mut NoopDelay::new()).unwrap();
+        assert_eq!(chosen, (Resolution::R176x144, (156, 128)));
+        assert_eq!(camera.configuration().image_format, ImageFormat::RGB565);
+        assert_eq!(camera.configuration().resolution, Resolution::R176x144);
+        // The output size is in units of 4 pixels
+        assert_eq!(sensor.register(DSP_BANK, ZMOW), 156 / 4);
+        assert_eq!(sensor.register(DSP_BANK, ZMOH), 128 / 4);
+        assert_eq!(sensor.register(DSP_BANK, ZMHH), 0x00);
+
+        // A display matching a resolution keeps the output of its table
+        let mut camera = OV2640::<_, SpiMock<u8>>::new(Some(&mut sensor), None);
+        let chosen = camera.set_for_display(320, 240, &mut NoopDelay::new()).unwrap();
+        assert_eq!(chosen, (Resolution::R320x240, (320, 240)));
+        assert!(camera.verify_configuration().unwrap());
+    }
+}
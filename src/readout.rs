@@ -0,0 +1,212 @@
+//!
+//! Image readout abstraction, so frames can be read from the ArduChip SPI FIFO
+//! or from another data path such as a parallel DCMI interface
+//!
+
+use crate::config::ImageFormat;
+
+/// A captured image together with the format and dimensions it was captured
+/// with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub format: ImageFormat,
+    pub width: u16,
+    pub height: u16,
+    // image bytes without the fifo padding
+    pub data: &'a [u8],
+}
+
+/// A source of captured frames.
+///
+/// The OV2640 is configured over I2C (SCCB) whatever the data path, so a
+/// parallel DCMI backend keeps an `OV2640` for the configuration (without an
+/// SPI peripheral) and implements `FrameReader` on top of its DCMI / DMA
+/// peripheral, starting a frame capture on VSYNC, reporting it done once the
+/// DMA transfer completes and copying the received bytes out in `read_frame`.
+/// `OV2640` implements `FrameReader` over the ArduChip SPI FIFO
+pub trait FrameReader {
+    type Error;
+
+    /// Start capturing a frame
+    fn start_frame(&mut self) -> Result<(), Self::Error>;
+
+    /// Check whether the frame has been captured
+    fn frame_done(&mut self) -> Result<bool, Self::Error>;
+
+    /// Read the captured frame into the provided buffer, returning the frame
+    /// length in bytes
+    fn read_frame(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Walk the header of a captured JPEG and call `rewrite` with the id and the
+/// values of each quantization table of its DQT (0xFFDB) segments, for
+/// decoders that reject the tables the OV2640 encoder writes.  The values are
+/// in zigzag order, one byte each for 8-bit tables and two (high byte first)
+/// for 16-bit tables.  Returns the number of tables found before the start of
+/// scan, or `None` if the JPEG has no start of image marker or a truncated
+/// header segment
+pub fn rewrite_quantization_tables(
+    jpeg: &mut [u8], mut rewrite: impl FnMut(u8, &mut [u8])
+) -> Option<usize> {
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut tables = 0;
+    let mut position = 2;
+    loop {
+        if *jpeg.get(position)? != 0xFF {
+            return None;
+        }
+        let marker = *jpeg.get(position + 1)?;
+        match marker {
+            // Fill byte before a marker
+            0xFF => {
+                position += 1;
+                continue;
+            },
+            // Start of scan or end of image, the header is over
+            0xDA | 0xD9 => return Some(tables),
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => {
+                position += 2;
+                continue;
+            },
+            _ => {},
+        }
+
+        let length = u16::from_be_bytes([*jpeg.get(position + 2)?, *jpeg.get(position + 3)?]) as usize;
+        let segment = jpeg.get_mut(position + 4..position + 2 + length.max(2))?;
+        if marker == 0xDB {
+            let mut offset = 0;
+            while offset < segment.len() {
+                let (precision, id) = (segment[offset] >> 4, segment[offset] & 0x0F);
+                let size = if precision == 0 { 64 } else { 128 };
+                rewrite(id, segment.get_mut(offset + 1..offset + 1 + size)?);
+                tables += 1;
+                offset += 1 + size;
+            }
+        }
+        position += 2 + length;
+    }
+}
+
+#[cfg(feature = "image")]
+impl Frame<'_> {
+    /// Decode a JPEG or RGB565 frame (with the high byte of each pixel first)
+    /// into an `image::DynamicImage`, other formats are unsupported
+    pub fn to_dynamic_image(&self) -> Result<image::DynamicImage, image::ImageError> {
+        use alloc::string::ToString;
+        use image::error::{
+            ImageError, ImageFormatHint, ParameterError, ParameterErrorKind,
+            UnsupportedError, UnsupportedErrorKind,
+        };
+
+        let dimension_mismatch = || ImageError::Parameter(
+            ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)
+        );
+        match self.format {
+            ImageFormat::JPEG => {
+                image::load_from_memory_with_format(self.data, image::ImageFormat::Jpeg)
+            },
+            ImageFormat::RGB565 => {
+                let pixels = self.width as usize * self.height as usize;
+                let data = self.data.get(..pixels * 2).ok_or_else(dimension_mismatch)?;
+
+                let mut rgb = alloc::vec::Vec::with_capacity(pixels * 3);
+                for pixel in data.chunks_exact(2) {
+                    let value = u16::from_be_bytes([pixel[0], pixel[1]]);
+                    let r = (value >> 11) as u8 & 0x1F;
+                    let g = (value >> 5) as u8 & 0x3F;
+                    let b = value as u8 & 0x1F;
+                    rgb.extend_from_slice(&[(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]);
+                }
+                image::RgbImage::from_raw(self.width as u32, self.height as u32, rgb)
+                    .map(image::DynamicImage::ImageRgb8)
+                    .ok_or_else(dimension_mismatch)
+            },
+            _ => Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Unknown,
+                UnsupportedErrorKind::GenericFeature("only jpeg and rgb565 frames can be decoded".to_string()),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn rgb565_frames_decode_to_rgb8() {
+        // Red, green, blue and white pixels, high byte first
+        let data = [0xF8, 0x00, 0x07, 0xE0, 0x00, 0x1F, 0xFF, 0xFF];
+        let frame = Frame { format: ImageFormat::RGB565, width: 2, height: 2, data: &data };
+
+        let image = frame.to_dynamic_image().unwrap().into_rgb8();
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(image.into_raw(), [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn short_and_unsupported_frames_fail_to_decode() {
+        let data = [0xF8, 0x00];
+        let short = Frame { format: ImageFormat::RGB565, width: 2, height: 2, data: &data };
+        assert!(matches!(short.to_dynamic_image(), Err(image::ImageError::Parameter(_))));
+
+        let grayscale = Frame { format: ImageFormat::Grayscale, width: 2, height: 1, data: &data };
+        assert!(matches!(grayscale.to_dynamic_image(), Err(image::ImageError::Unsupported(_))));
+    }
+
+    /// JPEG header with a JFIF APP0 segment, one DQT segment holding an 8-bit
+    /// luminance and an 8-bit chrominance table, a second DQT segment holding a
+    /// 16-bit table and a start of scan, followed by entropy coded data
+    fn jpeg_header() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend([0xFF, 0xE0, 0x00, 0x10]);
+        jpeg.extend(b"JFIF\0");
+        jpeg.extend([0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00]);
+        jpeg.extend([0xFF, 0xDB, 0x00, 0x84, 0x00]);
+        jpeg.extend([0x10; 64]);
+        jpeg.push(0x01);
+        jpeg.extend([0x20; 64]);
+        jpeg.extend([0xFF, 0xDB, 0x00, 0x83, 0x12]);
+        jpeg.extend([0x00, 0x30].repeat(64));
+        jpeg.extend([0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xDB, 0x00, 0x43, 0x00]);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn quantization_tables_are_rewritten_in_place() {
+        let mut jpeg = jpeg_header();
+        let original = jpeg.clone();
+        let mut ids = Vec::new();
+
+        let tables = rewrite_quantization_tables(&mut jpeg, |id, table| {
+            ids.push((id, table.len()));
+            table.fill(0x01);
+        });
+        assert_eq!(tables, Some(3));
+        assert_eq!(ids, [(0, 64), (1, 64), (2, 128)]);
+        assert_eq!(jpeg[25..89], [0x01; 64]);
+        assert_eq!(jpeg[90..154], [0x01; 64]);
+        assert_eq!(jpeg[159..287], [0x01; 128]);
+        // Everything but the table values is left as it was, including the
+        // marker bytes in the entropy coded data
+        assert_eq!(jpeg[..25], original[..25]);
+        assert_eq!(jpeg[287..], original[287..]);
+    }
+
+    #[test]
+    fn malformed_jpeg_headers_are_rejected() {
+        let mut no_soi = jpeg_header()[2..].to_vec();
+        assert_eq!(rewrite_quantization_tables(&mut no_soi, |_, _| {}), None);
+
+        // Cut off part way through the second table
+        let mut truncated = jpeg_header()[..120].to_vec();
+        assert_eq!(rewrite_quantization_tables(&mut truncated, |_, _| {}), None);
+    }
+}
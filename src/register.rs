@@ -1,19 +1,238 @@
 //!
 //! Default Register Definitions for the OV2640 Camera Module
 //!
+//! The register addresses and bits are public for use with the raw register
+//! API, grouped into the ArduChip registers and the DSP and sensor banks of
+//! the sensor.  The initialization tables are private to the driver
+//!
+//! ```
+//! use embedded_hal::{i2c::I2c, spi::SpiDevice};
+//! use ov2640::{OV2640, OV2640Error, SENSOR_BANK};
+//! use ov2640::register::sensor::{CHIP_ID_HIGH, CHIP_ID_LOW};
+//!
+//! fn chip_id<I2C: I2c, SPI: SpiDevice>(
+//!     camera: &mut OV2640<I2C, SPI>
+//! ) -> Result<u16, OV2640Error<I2C::Error, SPI::Error>> {
+//!     let high = camera.read_sensor_register(SENSOR_BANK, CHIP_ID_HIGH)?;
+//!     let low = camera.read_sensor_register(SENSOR_BANK, CHIP_ID_LOW)?;
+//!     Ok(u16::from_be_bytes([high, low]))
+//! }
+//! ```
+//!
 
-#![allow(unused)]
 #![allow(non_upper_case_globals)]
 
-pub(crate) const TEST_REGISTER: u8 = 0x00;
-pub(crate) const FIFO: u8 = 0x04;
-pub(crate) const GPIO: u8 = 0x06;
-pub(crate) const CHIP_ID_HIGH: u8 = 0x0A;
-pub(crate) const CHIP_ID_LOW: u8 = 0x0B;
-pub(crate) const FIFO_SIZE_1: u8 = 0x42;
-pub(crate) const FIFO_SIZE_2: u8 = 0x43;
-pub(crate) const FIFO_SIZE_3: u8 = 0x44;
-pub(crate) const TRIGGER: u8 = 0x41;
+/// ArduChip registers, accessed over SPI
+pub mod arduchip {
+    pub const TEST_REGISTER: u8 = 0x00;
+    pub const FIFO: u8 = 0x04;
+    pub const GPIO: u8 = 0x06;
+    pub const TRIGGER: u8 = 0x41;
+    pub const FIFO_SIZE_1: u8 = 0x42;
+    pub const FIFO_SIZE_2: u8 = 0x43;
+    pub const FIFO_SIZE_3: u8 = 0x44;
+}
+
+/// DSP bank (0xFF = 0x00) registers of the sensor, accessed over I2C
+pub mod dsp {
+    pub const R_BYPASS: u8 = 0x05;
+    pub const QS: u8 = 0x44;
+    pub const HSIZE8: u8 = 0xC0;
+    pub const VSIZE8: u8 = 0xC1;
+    pub const HSIZE: u8 = 0x51;
+    pub const VSIZE: u8 = 0x52;
+    pub const XOFFL: u8 = 0x53;
+    pub const YOFFL: u8 = 0x54;
+    pub const VHYX: u8 = 0x55;
+    pub const TEST: u8 = 0x57;
+    pub const ZMOW: u8 = 0x5A;
+    pub const ZMOH: u8 = 0x5B;
+    pub const ZMHH: u8 = 0x5C;
+    pub const GAMMA_ADDR: u8 = 0x90;
+    pub const GAMMA_DATA: u8 = 0x91;
+    pub const SHARPNESS_ADDR: u8 = 0x92;
+    pub const SHARPNESS_DATA: u8 = 0x93;
+    pub const CTRL1: u8 = 0xC3;
+    pub const AWB_CTRL: u8 = 0xC7;
+    pub const AWB_R_GAIN: u8 = 0xCC;
+    pub const AWB_G_GAIN: u8 = 0xCD;
+    pub const AWB_B_GAIN: u8 = 0xCE;
+    pub const IMAGE_MODE: u8 = 0xDA;
+    pub const RESET: u8 = 0xE0;
+
+    // IMAGE_MODE Bits
+    pub const IMAGE_MODE_BYTE_SWAP: u8 = 0x01;
+
+    // RESET Bits
+    pub const RESET_DVP: u8 = 0x04;
+
+    // SHARPNESS_DATA Bits
+    pub const SHARPNESS_AUTO: u8 = 0x20;
+    pub const SHARPNESS_MANUAL: u8 = 0xC0;
+
+    // R_BYPASS Bits
+    pub const R_BYPASS_DSP: u8 = 0x01;
+
+    // CTRL1 Bits
+    pub const CTRL1_LENC: u8 = 0x02;
+
+    // AWB_CTRL Bits
+    pub const AWB_CTRL_MANUAL: u8 = 0x40;
+}
+
+/// Sensor bank (0xFF = 0x01) registers of the sensor, accessed over I2C
+pub mod sensor {
+    pub const COM1: u8 = 0x03;
+    pub const REG04: u8 = 0x04;
+    pub const COM2: u8 = 0x09;
+    pub const CHIP_ID_HIGH: u8 = 0x0A;
+    pub const CHIP_ID_LOW: u8 = 0x0B;
+    pub const COM3: u8 = 0x0C;
+    pub const AEC: u8 = 0x10;
+    pub const CLKRC: u8 = 0x11;
+    pub const COM7: u8 = 0x12;
+    pub const COM8: u8 = 0x13;
+    pub const COM9: u8 = 0x14;
+    pub const COM10: u8 = 0x15;
+    pub const YAVG: u8 = 0x2F;
+    pub const REG45: u8 = 0x45;
+    pub const COM22: u8 = 0x4B;
+    pub const BD50: u8 = 0x4F;
+    pub const BD60: u8 = 0x50;
+
+    // COM1 Bits
+    pub const COM1_NIGHT_MODE: u8 = 0xC0;
+
+    // COM2 Bits
+    pub const COM2_STANDBY: u8 = 0x10;
+
+    // REG04 Bits
+    pub const REG04_HFLIP: u8 = 0x80;
+    pub const REG04_VFLIP: u8 = 0x40;
+    pub const REG04_VREF_EN: u8 = 0x10;
+
+    // COM3 Bits
+    pub const COM3_DEFAULT: u8 = 0x38;
+    pub const COM3_BAND_50HZ: u8 = 0x04;
+    pub const COM3_BAND_AUTO: u8 = 0x02;
+    pub const COM3_BAND_MASK: u8 = COM3_BAND_50HZ | COM3_BAND_AUTO;
+
+    // COM7 Bits
+    pub const COM7_COLOR_BAR: u8 = 0x02;
+
+    // COM8 Bits
+    pub const COM8_BNDF_EN: u8 = 0x20;
+    pub const COM8_AGC_EN: u8 = 0x04;
+    pub const COM8_AEC_EN: u8 = 0x01;
+
+    // COM10 Bits
+    pub const COM10_PCLK_RISING: u8 = 0x10;
+    pub const COM10_HREF_NEGATIVE: u8 = 0x08;
+    pub const COM10_VSYNC_NEGATIVE: u8 = 0x02;
+}
+
+/// A table of `N` `[register, value]` writes, checked when built to start
+/// with a bank select, to only select the DSP (0x00) or sensor (0x01) bank and
+/// to have any `[0xFF, 0xFF]` end marker as its last entry
+///
+/// ```
+/// use embedded_hal::{i2c::I2c, spi::SpiDevice};
+/// use ov2640::{OV2640, OV2640Error, RegisterTable};
+///
+/// const TUNING: RegisterTable<3> = RegisterTable::new([
+///     [0xFF, 0x00],
+///     [0x7C, 0x00],
+///     [0xFF, 0xFF],
+/// ]);
+/// assert_eq!(TUNING.len(), 3);
+///
+/// fn tune<I2C: I2c, SPI: SpiDevice>(
+///     camera: &mut OV2640<I2C, SPI>
+/// ) -> Result<(), OV2640Error<I2C::Error, SPI::Error>> {
+///     camera.apply_register_table(&TUNING)
+/// }
+/// ```
+///
+/// A `const` table selecting a bank other than the DSP or sensor bank fails to
+/// compile
+///
+/// ```compile_fail
+/// use ov2640::RegisterTable;
+///
+/// const TUNING: RegisterTable<2> = RegisterTable::new([
+///     [0xFF, 0x02],
+///     [0x7C, 0x00],
+/// ]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegisterTable<const N: usize>([[u8; 2]; N]);
+
+impl<const N: usize> RegisterTable<N> {
+    /// Build a register table, panicking on a missing, invalid or misplaced
+    /// bank select (at compile time for a `const` table)
+    pub const fn new(entries: [[u8; 2]; N]) -> Self {
+        assert!(N > 0 && entries[0][0] == 0xFF, "register table must start with a bank select");
+        let mut index = 0;
+        while index < N {
+            let [register, value] = entries[index];
+            if register == 0xFF {
+                let end_marker = value == 0xFF && index == N - 1;
+                assert!(value <= 0x01 || end_marker, "register table selects an invalid bank");
+            }
+            index += 1;
+        }
+        Self(entries)
+    }
+
+    /// Get the number of entries in the table
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Check whether the table has no entries, never true for a built table
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Get the entries of the table
+    pub const fn entries(&self) -> &[[u8; 2]; N] {
+        &self.0
+    }
+
+    /// Write the table, skipping redundant bank selects
+    pub(crate) fn write<R: crate::access::RegisterAccess>(
+        &self, access: &mut R
+    ) -> Result<(), R::Error> {
+        access.write_many(&self.0)
+    }
+}
+
+impl<const N: usize> core::ops::Deref for RegisterTable<N> {
+    type Target = [[u8; 2]];
+
+    fn deref(&self) -> &[[u8; 2]] {
+        &self.0
+    }
+}
+
+// Documented DSP Bank registers that return meaningful values when read
+pub(crate) const DSP_READABLE_REGISTERS: [u8; 37] = [
+    0x05, 0x44, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55,
+    0x56, 0x57, 0x5A, 0x5B, 0x5C, 0x7C, 0x86, 0x87,
+    0x8C, 0xC0, 0xC1, 0xC2, 0xC3, 0xC7, 0xCC, 0xCD,
+    0xCE, 0xD3, 0xDA, 0xF0, 0xF7, 0xF8, 0xF9, 0xFA,
+    0xFB, 0xFC, 0xFD, 0xFE, 0xFF,
+];
+
+// Documented Sensor Bank registers that return meaningful values when read
+pub(crate) const SENSOR_READABLE_REGISTERS: [u8; 46] = [
+    0x00, 0x03, 0x04, 0x08, 0x09, 0x0A, 0x0B, 0x0C,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x17, 0x18,
+    0x19, 0x1A, 0x1C, 0x1D, 0x24, 0x25, 0x26, 0x2A,
+    0x2B, 0x2D, 0x2E, 0x2F, 0x32, 0x34, 0x45, 0x46,
+    0x47, 0x48, 0x49, 0x4B, 0x4E, 0x4F, 0x50, 0x5D,
+    0x5E, 0x5F, 0x60, 0x61, 0x62, 0xFF,
+];
 
 pub(crate) const QVGA_REGISTERS: [[u8; 2]; 194] = [
     [0xff, 0x0],
@@ -226,7 +445,7 @@ pub(crate) const JPEG_INIT_REGISTER: [[u8; 2]; 191] = [
     [0x2c, 0x0c],
     [0x33, 0x78],
     [0x3a, 0x33],
-    [0x3b, 0xfB],
+    [0x3b, 0xfb],
     [0x3e, 0x00],
     [0x43, 0x11],
     [0x16, 0x10],
@@ -419,6 +638,36 @@ pub(crate) const YUV422_REGISTERS: [[u8; 2]; 10] = [
     [0xff, 0xff],
 ];
 
+pub(crate) const RGB565_REGISTERS: [[u8; 2]; 7] = [
+    [0xff, 0x00],
+    [0x05, 0x01],
+    [0xda, 0x08],
+    [0xd7, 0x03],
+    [0xe0, 0x00],
+    [0x05, 0x00],
+    [0xff, 0xff],
+];
+
+pub(crate) const YUV422_OUTPUT_REGISTERS: [[u8; 2]; 7] = [
+    [0xff, 0x00],
+    [0x05, 0x01],
+    [0xda, 0x00],
+    [0xd7, 0x03],
+    [0xe0, 0x00],
+    [0x05, 0x00],
+    [0xff, 0xff],
+];
+
+pub(crate) const GRAYSCALE_REGISTERS: [[u8; 2]; 7] = [
+    [0xff, 0x00],
+    [0x05, 0x01],
+    [0xda, 0x40],
+    [0xd7, 0x03],
+    [0xe0, 0x00],
+    [0x05, 0x00],
+    [0xff, 0xff],
+];
+
 pub(crate) const JPEG_REGISTERS: [[u8; 2]; 9] = [
     [0xe0, 0x14],
     [0xe1, 0x77],
@@ -431,8 +680,96 @@ pub(crate) const JPEG_REGISTERS: [[u8; 2]; 9] = [
     [0xff, 0xff],
 ];
 
+/* JPG 96x96, from a centered 600x600 window */
+pub(crate) const JPEG_96x96_REGISTERS: RegisterTable<40> = RegisterTable::new([
+    [0xff, 0x01],
+    [0x12, 0x40],
+    [0x17, 0x11],
+    [0x18, 0x43],
+    [0x19, 0x00],
+    [0x1a, 0x4b],
+    [0x32, 0x09],
+    [0x4f, 0xca],
+    [0x50, 0xa8],
+    [0x5a, 0x23],
+    [0x6d, 0x00],
+    [0x39, 0x12],
+    [0x35, 0xda],
+    [0x22, 0x1a],
+    [0x37, 0xc3],
+    [0x23, 0x00],
+    [0x34, 0xc0],
+    [0x36, 0x1a],
+    [0x06, 0x88],
+    [0x07, 0xc0],
+    [0x0d, 0x87],
+    [0x0e, 0x41],
+    [0x4c, 0x00],
+    [0xff, 0x00],
+    [0xe0, 0x04],
+    [0xc0, 0x4b],
+    [0xc1, 0x4b],
+    [0x86, 0x35],
+    [0x50, 0x92],
+    [0x51, 0x96],
+    [0x52, 0x96],
+    [0x53, 0x64],
+    [0x54, 0x00],
+    [0x55, 0x00],
+    [0x57, 0x00],
+    [0x5a, 0x18],
+    [0x5b, 0x18],
+    [0x5c, 0x00],
+    [0xe0, 0x00],
+    [0xff, 0xff],
+]);
+
+/* JPG 128x128, from a centered 600x600 window */
+pub(crate) const JPEG_128x128_REGISTERS: RegisterTable<40> = RegisterTable::new([
+    [0xff, 0x01],
+    [0x12, 0x40],
+    [0x17, 0x11],
+    [0x18, 0x43],
+    [0x19, 0x00],
+    [0x1a, 0x4b],
+    [0x32, 0x09],
+    [0x4f, 0xca],
+    [0x50, 0xa8],
+    [0x5a, 0x23],
+    [0x6d, 0x00],
+    [0x39, 0x12],
+    [0x35, 0xda],
+    [0x22, 0x1a],
+    [0x37, 0xc3],
+    [0x23, 0x00],
+    [0x34, 0xc0],
+    [0x36, 0x1a],
+    [0x06, 0x88],
+    [0x07, 0xc0],
+    [0x0d, 0x87],
+    [0x0e, 0x41],
+    [0x4c, 0x00],
+    [0xff, 0x00],
+    [0xe0, 0x04],
+    [0xc0, 0x4b],
+    [0xc1, 0x4b],
+    [0x86, 0x35],
+    [0x50, 0x92],
+    [0x51, 0x96],
+    [0x52, 0x96],
+    [0x53, 0x64],
+    [0x54, 0x00],
+    [0x55, 0x00],
+    [0x57, 0x00],
+    [0x5a, 0x20],
+    [0x5b, 0x20],
+    [0x5c, 0x00],
+    [0xe0, 0x00],
+    [0xff, 0xff],
+]);
+
 /* JPG 160x120 */
-pub(crate) const JPEG_160x120_REGISTERS: [[u8; 2]; 40] = [
+pub(crate) const JPEG_160x120_REGISTERS: RegisterTable<40> = RegisterTable::new([
     [0xff, 0x01],
     [0x12, 0x40],
     [0x17, 0x11],
@@ -473,10 +810,10 @@ pub(crate) const JPEG_160x120_REGISTERS: [[u8; 2]; 40] = [
     [0x5c, 0x00],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
 /* JPG, 0x176x144 */
-pub(crate) const JPEG_176x144_REGISTERS: [[u8; 2]; 40] = [
+pub(crate) const JPEG_176x144_REGISTERS: RegisterTable<40> = RegisterTable::new([
     [0xff, 0x01],
     [0x12, 0x40],
     [0x17, 0x11],
@@ -517,10 +854,10 @@ pub(crate) const JPEG_176x144_REGISTERS: [[u8; 2]; 40] = [
     [0x5c, 0x00],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
 /* JPG 320x240 */
-pub(crate) const JPEG_320x240_REGISTERS: [[u8; 2]; 40] = [
+pub(crate) const JPEG_320x240_REGISTERS: RegisterTable<40> = RegisterTable::new([
     [0xff, 0x01],
     [0x12, 0x40],
     [0x17, 0x11],
@@ -561,10 +898,10 @@ pub(crate) const JPEG_320x240_REGISTERS: [[u8; 2]; 40] = [
     [0x5c, 0x00],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
 /* JPG 352x288 */
-pub(crate) const JPEG_352x288_REGISTERS: [[u8; 2]; 40] = [
+pub(crate) const JPEG_352x288_REGISTERS: RegisterTable<40> = RegisterTable::new([
     [0xff, 0x01],
     [0x12, 0x40],
     [0x17, 0x11],
@@ -605,10 +942,54 @@ pub(crate) const JPEG_352x288_REGISTERS: [[u8; 2]; 40] = [
     [0x5c, 0x00],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
+
+/* JPG 400x296 */
+pub(crate) const JPEG_400x296_REGISTERS: RegisterTable<40> = RegisterTable::new([
+    [0xff, 0x01],
+    [0x12, 0x40],
+    [0x17, 0x11],
+    [0x18, 0x43],
+    [0x19, 0x00],
+    [0x1a, 0x4b],
+    [0x32, 0x09],
+    [0x4f, 0xca],
+    [0x50, 0xa8],
+    [0x5a, 0x23],
+    [0x6d, 0x00],
+    [0x39, 0x12],
+    [0x35, 0xda],
+    [0x22, 0x1a],
+    [0x37, 0xc3],
+    [0x23, 0x00],
+    [0x34, 0xc0],
+    [0x36, 0x1a],
+    [0x06, 0x88],
+    [0x07, 0xc0],
+    [0x0d, 0x87],
+    [0x0e, 0x41],
+    [0x4c, 0x00],
+    [0xff, 0x00],
+    [0xe0, 0x04],
+    [0xc0, 0x64],
+    [0xc1, 0x4b],
+    [0x86, 0x35],
+    [0x50, 0x89],
+    [0x51, 0xc8],
+    [0x52, 0x96],
+    [0x53, 0x00],
+    [0x54, 0x00],
+    [0x55, 0x00],
+    [0x57, 0x00],
+    [0x5a, 0x64],
+    [0x5b, 0x4a],
+    [0x5c, 0x00],
+    [0xe0, 0x00],
+    [0xff, 0xff],
+]);
 
 /* JPG 640x480 */
-pub(crate) const JPEG_640x480_REGISTERS: [[u8; 2]; 41] = [
+pub(crate) const JPEG_640x480_REGISTERS: RegisterTable<41> = RegisterTable::new([
     [0xff, 0x01],
     [0x11, 0x01],
     [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
@@ -650,10 +1031,10 @@ pub(crate) const JPEG_640x480_REGISTERS: [[u8; 2]; 41] = [
     [0xd3, 0x04],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
 /* JPG 800x600 */
-pub(crate) const JPEG_800x600_REGISTERS: [[u8; 2]; 41] = [
+pub(crate) const JPEG_800x600_REGISTERS: RegisterTable<41> = RegisterTable::new([
     [0xff, 0x01],
     [0x11, 0x01],
     [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
@@ -695,10 +1076,10 @@ pub(crate) const JPEG_800x600_REGISTERS: [[u8; 2]; 41] = [
     [0xd3, 0x02],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
 /* JPG 1024x768 */
-pub(crate) const JPEG_1024x768_REGISTERS: [[u8; 2]; 39] = [
+pub(crate) const JPEG_1024x768_REGISTERS: RegisterTable<39> = RegisterTable::new([
     [0xff, 0x01],
     [0x11, 0x01],
     [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
@@ -738,10 +1119,10 @@ pub(crate) const JPEG_1024x768_REGISTERS: [[u8; 2]; 39] = [
     [0x5c, 0x01],
     [0xd3, 0x02],
     [0xff, 0xff],
-];
+]);
 
-/* JPG 1280x1024 */
-pub(crate) const JPEG_1280x1024_REGISTERS: [[u8; 2]; 41] = [
+/* JPG 1280x960 */
+pub(crate) const JPEG_1280x960_REGISTERS: RegisterTable<41> = RegisterTable::new([
     [0xff, 0x01],
     [0x11, 0x01],
     [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
@@ -783,10 +1164,55 @@ pub(crate) const JPEG_1280x1024_REGISTERS: [[u8; 2]; 41] = [
     [0xd3, 0x02],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
+
+/* JPG 1280x1024 */
+pub(crate) const JPEG_1280x1024_REGISTERS: RegisterTable<41> = RegisterTable::new([
+    [0xff, 0x01],
+    [0x11, 0x01],
+    [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
+    [0x17, 0x11], // HREFST[10:3]
+    [0x18, 0x75], // HREFEND[10:3]
+    [0x32, 0x36], // Bit[5:3]: HREFEND[2:0]; Bit[2:0]: HREFST[2:0]
+    [0x19, 0x01], // VSTRT[9:2]
+    [0x1a, 0x97], // VEND[9:2]
+    [0x03, 0x0f], // Bit[3:2]: VEND[1:0]; Bit[1:0]: VSTRT[1:0]
+    [0x37, 0x40],
+    [0x4f, 0xbb],
+    [0x50, 0x9c],
+    [0x5a, 0x57],
+    [0x6d, 0x80],
+    [0x3d, 0x34],
+    [0x39, 0x02],
+    [0x35, 0x88],
+    [0x22, 0x0a],
+    [0x37, 0x40],
+    [0x34, 0xa0],
+    [0x06, 0x02],
+    [0x0d, 0xb7],
+    [0x0e, 0x01],
+    [0xff, 0x00],
+    [0xe0, 0x04],
+    [0xc0, 0xc8],
+    [0xc1, 0x96],
+    [0x86, 0x3d],
+    [0x50, 0x00],
+    [0x51, 0x90],
+    [0x52, 0x2c],
+    [0x53, 0x00],
+    [0x54, 0x00],
+    [0x55, 0x88],
+    [0x57, 0x00],
+    [0x5a, 0x40],
+    [0x5b, 0x00], // OUTH[9:2]
+    [0x5c, 0x05], // Bit[2]: OUTH[10]; Bit[1:0]: OUTW[11:10]
+    [0xd3, 0x02],
+    [0xe0, 0x00],
+    [0xff, 0xff],
+]);
 
 /* JPG 1600x1200 */
-pub(crate) const JPEG_1600x1200_REGISTERS: [[u8; 2]; 41] = [
+pub(crate) const JPEG_1600x1200_REGISTERS: RegisterTable<41> = RegisterTable::new([
     [0xff, 0x01],
     [0x11, 0x01],
     [0x12, 0x00], // Bit[6:4]: Resolution selection//0x02Ϊ����
@@ -828,8 +1254,10 @@ pub(crate) const JPEG_1600x1200_REGISTERS: [[u8; 2]; 41] = [
     [0xd3, 0x02],
     [0xe0, 0x00],
     [0xff, 0xff],
-];
+]);
 
+// Alternative 800x600 initialization, unused by the driver
+#[allow(dead_code)]
 pub(crate) const SVGA_REGISTERS: [[u8; 2]; 178] = [
     [0xff, 0x01],
     [0x12, 0x80],
@@ -846,7 +1274,7 @@ pub(crate) const SVGA_REGISTERS: [[u8; 2]; 178] = [
     [0x2c, 0x0c],
     [0x33, 0x78],
     [0x3a, 0x33],
-    [0x3b, 0xfB],
+    [0x3b, 0xfb],
     [0x3e, 0x00],
     [0x43, 0x11],
     [0x16, 0x10],
@@ -1011,6 +1439,8 @@ pub(crate) const SVGA_REGISTERS: [[u8; 2]; 178] = [
     [0xff, 0xff],
 ];
 
+// Alternative 640x480 JPEG resolution table, unused by the driver
+#[allow(dead_code)]
 pub(crate) const JPEG2_640x480_REGISTERS: [[u8; 2]; 40] = [
     [0xff, 0x01], //001
     [0x11, 0x01], //002
@@ -1053,3 +1483,30 @@ pub(crate) const JPEG2_640x480_REGISTERS: [[u8; 2]; 40] = [
     [0xd3, 0x04], //039
     [0xe0, 0x00], //040
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_tables_keep_their_entries() {
+        let table = RegisterTable::new([[0xFF, 0x01], [0x11, 0x01], [0xFF, 0x00], [0x44, 0x0C]]);
+        assert_eq!(table.len(), 4);
+        assert!(!table.is_empty());
+        assert_eq!(table.entries()[1], [0x11, 0x01]);
+        assert_eq!(table[3], [0x44, 0x0C]);
+    }
+
+    #[test]
+    #[should_panic(expected = "register table must start with a bank select")]
+    fn register_tables_must_start_with_a_bank_select() {
+        RegisterTable::new([[0x11, 0x01], [0xFF, 0x00]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "register table selects an invalid bank")]
+    fn register_tables_must_not_end_early() {
+        // An end marker anywhere but the last entry
+        RegisterTable::new([[0xFF, 0x01], [0xFF, 0xFF], [0x11, 0x01]]);
+    }
+}
@@ -0,0 +1,709 @@
+//!
+//! Register write sequences for each configuration option, shared between the
+//! blocking and async drivers
+//!
+
+use crate::config::*;
+use crate::error::OV2640Error;
+use crate::register::*;
+use crate::register::{dsp::*, sensor::*};
+use crate::MAX_FIFO_SIZE;
+
+/// Lifecycle of a capture, so the FIFO is only read once a capture completed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CaptureState {
+    Idle,
+    Capturing,
+    Ready,
+}
+
+/// Width of the active sensor array in pixels
+const SENSOR_WIDTH: u16 = 1600;
+/// Height of the active sensor array in pixels
+const SENSOR_HEIGHT: u16 = 1200;
+
+/// Soft reset through the COM7 reset bit (followed by the reset delay)
+pub(crate) const SOFT_RESET_REGISTERS: RegisterTable<2> = RegisterTable::new([
+    [0xFF, 0x01],
+    [0x12, 0x80],
+]);
+
+/// Register tables written (after a soft reset) to select an image format
+pub(crate) fn image_format_tables(image_format: ImageFormat) -> &'static [&'static [[u8; 2]]] {
+    match image_format {
+        ImageFormat::JPEG => &[
+            &JPEG_INIT_REGISTER,
+            &YUV422_REGISTERS,
+            &JPEG_REGISTERS,
+            &[[0xFF, 0x01], [0x15, 0x00]],
+        ],
+        ImageFormat::QVGA => &[&QVGA_REGISTERS],
+        ImageFormat::RGB565 => &[&JPEG_INIT_REGISTER, &RGB565_REGISTERS],
+        // Y-only output of the YUV422 path
+        ImageFormat::Grayscale => &[&JPEG_INIT_REGISTER, &YUV422_REGISTERS, &GRAYSCALE_REGISTERS],
+        // YUV422 output with the JPEG output enable of IMAGE_MODE left clear
+        ImageFormat::YUV422 => &[&JPEG_INIT_REGISTER, &YUV422_OUTPUT_REGISTERS],
+    }
+}
+
+/// DSP bank registers read back by `verify_configuration`, the image format
+/// and the output size
+pub(crate) const VERIFIED_DSP_REGISTERS: [u8; 3] = [IMAGE_MODE, ZMOW, ZMOH];
+
+/// Value a register of the DSP bank is left at by the image format and
+/// resolution tables of a configuration, if they write it
+pub(crate) fn expected_dsp_register(configuration: &Configuration, register: u8) -> Option<u8> {
+    let value = table_register(configuration, 0x00, register);
+    if register == IMAGE_MODE && configuration.byte_swap {
+        return value.map(|value| value | IMAGE_MODE_BYTE_SWAP);
+    }
+    value
+}
+
+/// Value a register of a bank is left at by the image format and resolution
+/// tables of a configuration, if they write it
+pub(crate) fn table_register(configuration: &Configuration, register_bank: u8, register: u8) -> Option<u8> {
+    let format_tables = image_format_tables(configuration.image_format);
+    let resolution_table: &[[u8; 2]] = match configuration.image_format {
+        ImageFormat::QVGA => &[],
+        _ => resolution_registers(configuration.resolution),
+    };
+
+    let mut bank = None;
+    let mut value = None;
+    for table in format_tables.iter().chain(core::iter::once(&resolution_table)) {
+        for entry in table.iter().take_while(|entry| **entry != [0xFF, 0xFF]) {
+            if entry[0] == 0xFF {
+                bank = Some(entry[1]);
+            } else if bank == Some(register_bank) && entry[0] == register {
+                value = Some(entry[1]);
+            }
+        }
+    }
+    value
+}
+
+/// Get the next smaller resolution by pixel count, or `None` for the smallest
+pub(crate) fn lower_resolution(resolution: Resolution) -> Option<Resolution> {
+    let index = Resolution::ALL.iter().position(|&other| other == resolution)?;
+    index.checked_sub(1).map(|index| Resolution::ALL[index])
+}
+
+/// Choose the RGB565 resolution for a display and the output size it is scaled
+/// down to, keeping its aspect ratio, to fit within the display.  Of the
+/// resolutions whose frames fit the FIFO, those with the aspect ratio closest
+/// to the display's are considered and the smallest of them at least as large
+/// as the display is taken (or the largest if none is).  `None` if the scaled
+/// frame would be smaller than the 4 pixel unit of the output size
+pub(crate) fn display_resolution(
+    display_width: u16, display_height: u16
+) -> Option<(Resolution, (u16, u16))> {
+    let aspect_error = |resolution: Resolution| {
+        let (width, height) = resolution.dimensions();
+        (width as f32 / height as f32 - display_width as f32 / display_height as f32).abs()
+    };
+    let candidates = Resolution::ALL.iter()
+        .copied()
+        .filter(|&resolution| check_resolution::<(), ()>(ImageFormat::RGB565, resolution).is_ok());
+    let closest = candidates.clone().map(aspect_error).fold(f32::INFINITY, f32::min);
+    let mut matching = candidates.filter(|&resolution| aspect_error(resolution) == closest);
+
+    let resolution = matching.clone()
+        .find(|resolution| {
+            let (width, height) = resolution.dimensions();
+            width >= display_width || height >= display_height
+        })
+        .or_else(|| matching.next_back())?;
+    let (width, height) = fit_size(resolution.dimensions(), (display_width, display_height));
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((resolution, (width, height)))
+}
+
+/// Scale a frame size down, keeping its aspect ratio, to fit within a display,
+/// rounding down to the 4 pixel unit of the output size
+fn fit_size((width, height): (u16, u16), (display_width, display_height): (u16, u16)) -> (u16, u16) {
+    let (width, height) = (width as u32, height as u32);
+    let (display_width, display_height) = (display_width as u32, display_height as u32);
+    let (width, height) = if display_width * height <= display_height * width {
+        let fitted = width.min(display_width);
+        (fitted, fitted * height / width)
+    } else {
+        let fitted = height.min(display_height);
+        (fitted * width / height, fitted)
+    };
+    ((width & !0x03) as u16, (height & !0x03) as u16)
+}
+
+/// Check the DSP window (HSIZE, VSIZE, VHYX and TEST) and output (ZMOW, ZMOH
+/// and ZMHH) registers read back from the sensor, the output must be non-empty
+/// and fit within the window
+pub(crate) fn window_is_consistent(
+    [hsize, vsize, vhyx, test]: [u8; 4], [zmow, zmoh, zmhh]: [u8; 3]
+) -> bool {
+    let [hsize, vsize, vhyx, test] = [hsize, vsize, vhyx, test].map(u16::from);
+    let [zmow, zmoh, zmhh] = [zmow, zmoh, zmhh].map(u16::from);
+
+    // All sizes are in units of 4 pixels
+    let window_width = ((test & 0x80) << 2) | ((vhyx & 0x08) << 5) | hsize;
+    let window_height = ((vhyx & 0x80) << 1) | vsize;
+    let output_width = ((zmhh & 0x03) << 8) | zmow;
+    let output_height = ((zmhh & 0x04) << 6) | zmoh;
+
+    output_width != 0 && output_height != 0 &&
+        output_width <= window_width && output_height <= window_height
+}
+
+/// DSP output size (ZMOW, ZMOH and ZMHH) scaling the window of the current
+/// resolution table to `width` x `height`, each a multiple of 4 pixels
+pub(crate) fn output_size_registers(width: u16, height: u16) -> [[u8; 2]; 6] {
+    // Sizes are in units of 4 pixels
+    let width = width >> 2;
+    let height = height >> 2;
+    [
+        [0xFF, 0x00],
+        [RESET, RESET_DVP],
+        [ZMOW, width as u8],
+        [ZMOH, height as u8],
+        [ZMHH, (((height >> 6) & 0x04) | ((width >> 8) & 0x03)) as u8],
+        [RESET, 0x00],
+    ]
+}
+
+/// Check that a resolution can be used with an image format.  QVGA has a fixed
+/// resolution and uncompressed frames must fit within the FIFO
+pub(crate) fn check_resolution<I2CErr, SPIErr>(
+    image_format: ImageFormat, resolution: Resolution
+) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+    if image_format == ImageFormat::QVGA {
+        return Err(OV2640Error::ResolutionNotConfigurableForFormat);
+    }
+
+    if let Some(bytes_per_pixel) = image_format.bytes_per_pixel() {
+        let (width, height) = resolution.dimensions();
+        if width as usize * height as usize * bytes_per_pixel > MAX_FIFO_SIZE {
+            return Err(OV2640Error::ResolutionTooLargeForFifo);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn resolution_registers(resolution: Resolution) -> &'static [[u8; 2]] {
+    match resolution {
+        Resolution::R96x96 => &JPEG_96x96_REGISTERS,
+        Resolution::R128x128 => &JPEG_128x128_REGISTERS,
+        Resolution::R160x120 => &JPEG_160x120_REGISTERS,
+        Resolution::R176x144 => &JPEG_176x144_REGISTERS,
+        Resolution::R320x240 => &JPEG_320x240_REGISTERS,
+        Resolution::R352x288 => &JPEG_352x288_REGISTERS,
+        Resolution::R400x296 => &JPEG_400x296_REGISTERS,
+        Resolution::R640x480 => &JPEG_640x480_REGISTERS,
+        Resolution::R800x600 => &JPEG_800x600_REGISTERS,
+        Resolution::R1024x768 => &JPEG_1024x768_REGISTERS,
+        Resolution::R1280x960 => &JPEG_1280x960_REGISTERS,
+        Resolution::R1280x1024 => &JPEG_1280x1024_REGISTERS,
+        Resolution::R1600x1200 => &JPEG_1600x1200_REGISTERS,
+    }
+}
+
+pub(crate) fn light_mode_registers(light_mode: LightMode) -> &'static [[u8; 2]] {
+    match light_mode {
+        LightMode::Auto => &[[0xFF, 0x00], [0xC7, 0x00]],
+        LightMode::Sunny => &[[0xFF, 0x00], [0xC7, 0x40], [0xCC, 0x5E], [0xCD, 0x41], [0xCE, 0x54]],
+        LightMode::Cloudy => &[[0xFF, 0x00], [0xC7, 0x40], [0xCC, 0x65], [0xCD, 0x41], [0xCE, 0x4F]],
+        LightMode::Office => &[[0xFF, 0x00], [0xC7, 0x40], [0xCC, 0x52], [0xCD, 0x41], [0xCE, 0x6]],
+        LightMode::Home => &[[0xFF, 0x00], [0xC7, 0x40], [0xCC, 0x42], [0xCD, 0x3F], [0xCE, 0x71]],
+        LightMode::Incandescent => &[[0xFF, 0x00], [0xC7, 0x40], [0xCC, 0x3C], [0xCD, 0x40], [0xCE, 0x78]],
+    }
+}
+
+pub(crate) fn saturation_registers(saturation: Saturation) -> [[u8; 2]; 6] {
+    let level = match saturation {
+        Saturation::Saturation0 => 4,
+        Saturation::Saturation1 => 2,
+        Saturation::Saturation2 => 0,
+        Saturation::Saturation3 => -2,
+        Saturation::Saturation4 => -4,
+    };
+    saturation_level_registers(level)
+}
+
+/// Saturation registers for a level in `MIN_LEVEL..=MAX_LEVEL`, each
+/// `Saturation` step is two levels
+pub(crate) fn saturation_level_registers(level: i8) -> [[u8; 2]; 6] {
+    let value = (0x48 + 0x08 * level as i16) as u8;
+    [[0xFF, 0x00], [0x7C, 0x00], [0x7D, 0x02], [0x7C, 0x04], [0x7D, value], [0x7D, value]]
+}
+
+pub(crate) fn brightness_registers(brightness: Brightness) -> [[u8; 2]; 6] {
+    let level = match brightness {
+        Brightness::Brightness0 => 4,
+        Brightness::Brightness1 => 2,
+        Brightness::Brightness2 => 0,
+        Brightness::Brightness3 => -2,
+        Brightness::Brightness4 => -4,
+    };
+    brightness_level_registers(level)
+}
+
+/// Brightness registers for a level in `MIN_LEVEL..=MAX_LEVEL`, each
+/// `Brightness` step is two levels
+pub(crate) fn brightness_level_registers(level: i8) -> [[u8; 2]; 6] {
+    let value = (0x20 + 0x08 * level as i16) as u8;
+    [[0xFF, 0x00], [0x7C, 0x00], [0x7D, 0x04], [0x7C, 0x09], [0x7D, value], [0x7D, 0x00]]
+}
+
+pub(crate) fn contrast_registers(contrast: Contrast) -> [[u8; 2]; 8] {
+    let level = match contrast {
+        Contrast::Contrast0 => 4,
+        Contrast::Contrast1 => 2,
+        Contrast::Contrast2 => 0,
+        Contrast::Contrast3 => -2,
+        Contrast::Contrast4 => -4,
+    };
+    contrast_level_registers(level)
+}
+
+/// Contrast registers for a level in `MIN_LEVEL..=MAX_LEVEL`, each `Contrast`
+/// step is two levels.  Odd levels sit halfway between the preset values
+pub(crate) fn contrast_level_registers(level: i8) -> [[u8; 2]; 8] {
+    let (first, second) = match level {
+        i8::MIN..=-4 => (0x18, 0x34),
+        -3 => (0x1C, 0x2F),
+        -2 => (0x20, 0x2A),
+        -1 => (0x20, 0x25),
+        0 => (0x20, 0x20),
+        1 => (0x22, 0x1B),
+        2 => (0x24, 0x16),
+        3 => (0x26, 0x11),
+        4..=i8::MAX => (0x28, 0x0C),
+    };
+    [
+        [0xFF, 0x00], [0x7C, 0x00], [0x7D, 0x04], [0x7C, 0x07], [0x7D, 0x20],
+        [0x7D, first], [0x7D, second], [0x7D, 0x06],
+    ]
+}
+
+/// Check a fine-grained brightness, contrast or saturation level
+pub(crate) fn check_level<I2CErr, SPIErr>(
+    level: i8
+) -> Result<i8, OV2640Error<I2CErr, SPIErr>> {
+    if !(MIN_LEVEL..=MAX_LEVEL).contains(&level) {
+        return Err(OV2640Error::LevelOutOfRange);
+    }
+    Ok(level)
+}
+
+pub(crate) fn special_effect_registers(special_effect: SpecialEffect) -> [[u8; 2]; 6] {
+    let (enable, u, v) = match special_effect {
+        SpecialEffect::Antique | SpecialEffect::Sepia => (0x18, 0x40, 0xA6),
+        SpecialEffect::Bluish => (0x18, 0xA0, 0x40),
+        SpecialEffect::Greenish => (0x18, 0x40, 0x40),
+        SpecialEffect::Reddish => (0x18, 0x40, 0xC0),
+        SpecialEffect::BlackWhite => (0x18, 0x80, 0x80),
+        SpecialEffect::Negative => (0x40, 0x80, 0x80),
+        SpecialEffect::BlackWhiteNegative => (0x58, 0x80, 0x80),
+        SpecialEffect::Normal => (0x00, 0x80, 0x80),
+    };
+    special_effect_uv_registers(enable, u, v)
+}
+
+/// Special effect registers enabling the effects in `enable` with the fixed U
+/// and V values used by the tint effects
+pub(crate) fn special_effect_uv_registers(enable: u8, u: u8, v: u8) -> [[u8; 2]; 6] {
+    [[0xFF, 0x00], [0x7C, 0x00], [0x7D, enable], [0x7C, 0x05], [0x7D, u], [0x7D, v]]
+}
+
+pub(crate) fn jpeg_quality_registers(jpeg_quality: JpegQuality) -> [[u8; 2]; 2] {
+    let value = match jpeg_quality {
+        JpegQuality::High => 0x04,
+        JpegQuality::Medium => 0x0C,
+        JpegQuality::Low => 0x20,
+    };
+    [[0xFF, 0x00], [QS, value]]
+}
+
+/// Check a (high, low) product ID is that of an OV2640, 0x26 low with a high
+/// byte of 0x41 or 0x42 depending on the silicon revision
+pub(crate) fn is_ov2640_product_id(high: u8, low: u8) -> bool {
+    low == 0x26 && (high == 0x41 || high == 0x42)
+}
+
+/// Bits of REG04 set by a flip
+pub(crate) const FLIP_REG04_MASK: u8 = REG04_HFLIP | REG04_VFLIP | REG04_VREF_EN;
+
+/// Apply a flip to the current value of REG04 (read from the sensor bank)
+pub(crate) fn flip_reg04(reg04: u8, flip: Flip) -> u8 {
+    let mut reg04 = reg04 & !FLIP_REG04_MASK;
+    if flip.horizontal {
+        reg04 |= REG04_HFLIP;
+    }
+    if flip.vertical {
+        // The vertical window is shifted a line to keep the bayer order
+        reg04 |= REG04_VFLIP | REG04_VREF_EN;
+    }
+    reg04
+}
+
+pub(crate) fn strobe_registers(strobe: StrobeMode) -> [[u8; 2]; 2] {
+    let value = match strobe {
+        StrobeMode::Off => 0x20,
+        StrobeMode::Custom(value) => value,
+    };
+    [[0xFF, 0x01], [COM22, value]]
+}
+
+/// Gamma curve through the indirect gamma registers of the DSP bank.  Writing
+/// the address register rewinds to the first point and each data write
+/// advances to the next
+pub(crate) fn gamma_curve_registers(
+    points: &[u8; GAMMA_CURVE_POINTS]
+) -> [[u8; 2]; GAMMA_CURVE_POINTS + 2] {
+    let mut registers = [[GAMMA_DATA, 0x00]; GAMMA_CURVE_POINTS + 2];
+    registers[0] = [0xFF, 0x00];
+    registers[1] = [GAMMA_ADDR, 0x00];
+    for (register, point) in registers[2..].iter_mut().zip(points) {
+        register[1] = *point;
+    }
+    registers
+}
+
+/// Edge enhancement through the indirect sharpness registers of the DSP bank
+pub(crate) fn sharpness_registers<I2CErr, SPIErr>(
+    sharpness: Sharpness
+) -> Result<[[u8; 2]; 3], OV2640Error<I2CErr, SPIErr>> {
+    let value = match sharpness {
+        Sharpness::Auto => SHARPNESS_AUTO,
+        Sharpness::Manual(level) if level <= MAX_SHARPNESS => SHARPNESS_MANUAL | level,
+        Sharpness::Manual(_) => return Err(OV2640Error::LevelOutOfRange),
+    };
+    Ok([[0xFF, 0x00], [SHARPNESS_ADDR, 0x01], [SHARPNESS_DATA, value]])
+}
+
+/// Manual white balance gains in the DSP bank.  Auto white balance is the same
+/// as `LightMode::Auto`
+pub(crate) fn manual_white_balance_registers(r_gain: u8, g_gain: u8, b_gain: u8) -> [[u8; 2]; 5] {
+    [[0xFF, 0x00], [AWB_CTRL, AWB_CTRL_MANUAL], [AWB_R_GAIN, r_gain], [AWB_G_GAIN, g_gain], [AWB_B_GAIN, b_gain]]
+}
+
+/// The 50Hz / 60Hz band steps (BD50 and BD60 in the sensor bank) for the pixel
+/// clock of a configuration, as set by its resolution table.  Every image
+/// format table writes both, so the fallbacks (the UXGA steps) are not used
+pub(crate) fn band_step_registers(configuration: &Configuration) -> [[u8; 2]; 3] {
+    let bd50 = table_register(configuration, 0x01, BD50).unwrap_or(0xBB);
+    let bd60 = table_register(configuration, 0x01, BD60).unwrap_or(0x9C);
+    [[0xFF, 0x01], [BD50, bd50], [BD60, bd60]]
+}
+
+/// Frequency selection bits of COM3 (within `COM3_BAND_MASK`) for a banding
+/// filter
+pub(crate) fn com3_band_bits(banding_filter: BandingFilter) -> u8 {
+    match banding_filter {
+        BandingFilter::Disabled | BandingFilter::Hz60 => 0x00,
+        BandingFilter::Hz50 => COM3_BAND_50HZ,
+        BandingFilter::Auto => COM3_BAND_AUTO,
+    }
+}
+
+/// Get the manual frequency COM3 selects underneath its auto detect bit once
+/// a banding filter is set, which takes effect when auto banding is turned off
+pub(crate) fn manual_banding_filter(banding_filter: BandingFilter) -> BandingFilter {
+    match banding_filter {
+        BandingFilter::Hz50 => BandingFilter::Hz50,
+        _ => BandingFilter::Hz60,
+    }
+}
+
+/// Apply a banding filter to the current value of COM8 (read from the sensor
+/// bank)
+pub(crate) fn banding_filter_com8(com8: u8, banding_filter: BandingFilter) -> u8 {
+    match banding_filter {
+        BandingFilter::Disabled => com8 & !COM8_BNDF_EN,
+        _ => com8 | COM8_BNDF_EN,
+    }
+}
+
+/// DSP window and output size registers for a crop of the sensor array.  The
+/// output size is the same as the window so the crop is not scaled
+pub(crate) fn window_registers<I2CErr, SPIErr>(
+    x: u16, y: u16, width: u16, height: u16
+) -> Result<[[u8; 2]; 12], OV2640Error<I2CErr, SPIErr>> {
+    if width == 0 || height == 0 || !width.is_multiple_of(4) || !height.is_multiple_of(4) ||
+        x as u32 + width as u32 > SENSOR_WIDTH as u32 ||
+        y as u32 + height as u32 > SENSOR_HEIGHT as u32 {
+        return Err(OV2640Error::InvalidWindow);
+    }
+
+    // Sizes are in units of 4 pixels
+    let width = width >> 2;
+    let height = height >> 2;
+    let vhyx = ((height >> 1) & 0x80) | ((y >> 4) & 0x70) | ((width >> 5) & 0x08) | ((x >> 8) & 0x07);
+    Ok([
+        [0xFF, 0x00],
+        [RESET, RESET_DVP],
+        [HSIZE, width as u8],
+        [VSIZE, height as u8],
+        [XOFFL, x as u8],
+        [YOFFL, y as u8],
+        [VHYX, vhyx as u8],
+        [TEST, ((width >> 2) & 0x80) as u8],
+        [ZMOW, width as u8],
+        [ZMOH, height as u8],
+        [ZMHH, (((height >> 6) & 0x04) | ((width >> 8) & 0x03)) as u8],
+        [RESET, 0x00],
+    ])
+}
+
+/// Length of the image in a FIFO holding `fifo_length` bytes.  Uncompressed
+/// frames are exactly width x height pixels while the FIFO may hold trailing
+/// bytes
+pub(crate) fn image_length(configuration: &Configuration, fifo_length: usize) -> usize {
+    match configuration.image_format.bytes_per_pixel() {
+        Some(bytes_per_pixel) if configuration.image_format != ImageFormat::QVGA => {
+            let (width, height) = configuration.resolution.dimensions();
+            fifo_length.min(width as usize * height as usize * bytes_per_pixel)
+        },
+        _ => fifo_length,
+    }
+}
+
+/// Byte offset and length of `rows` scanlines from `start_row` of an
+/// uncompressed frame, or `None` for JPEG or scanlines outside the frame
+pub(crate) fn scanline_band(
+    configuration: &Configuration, start_row: u16, rows: u16
+) -> Option<(usize, usize)> {
+    let bytes_per_pixel = configuration.image_format.bytes_per_pixel()?;
+    let (width, height) = configuration.frame_dimensions();
+    if rows == 0 || start_row as u32 + rows as u32 > height as u32 {
+        return None;
+    }
+
+    let row_length = width as usize * bytes_per_pixel;
+    Some((start_row as usize * row_length, rows as usize * row_length))
+}
+
+/// Iterate over the entries of a register table up to its `[0xFF, 0xFF]` end
+/// marker (if any)
+pub(crate) fn table_entries(registers: &[[u8; 2]]) -> impl Iterator<Item = &[u8; 2]> {
+    registers.iter().take_while(|register| **register != [0xFF, 0xFF])
+}
+
+/// Remove a dummy byte in front of a JPEG's start of image marker, returning
+/// the new image length
+pub(crate) fn strip_dummy_byte(image_format: ImageFormat, image: &mut [u8]) -> usize {
+    if image_format == ImageFormat::JPEG && !image.starts_with(&[0xFF, 0xD8]) &&
+        image.get(1..3) == Some(&[0xFF, 0xD8]) {
+        image.copy_within(1.., 0);
+        return image.len() - 1;
+    }
+    image.len()
+}
+
+/// Find the end (just after the 0xFFD9 end of image marker) of the JPEG being
+/// read into `image`, scanning from `scan_start`
+pub(crate) fn jpeg_end(image: &[u8], scan_start: usize) -> Option<usize> {
+    image[scan_start..].windows(2)
+        .position(|bytes| bytes == [0xFF, 0xD9])
+        .map(|position| scan_start + position + 2)
+}
+
+/// Get the readout throughput in bytes per second of an image read in
+/// `elapsed_us` microseconds, `None` if no time was measured
+pub(crate) fn read_throughput(image_size: usize, elapsed_us: u32) -> Option<u32> {
+    match elapsed_us {
+        0 => None,
+        elapsed_us => Some(
+            (image_size as u64 * 1_000_000 / elapsed_us as u64).min(u32::MAX as u64) as u32
+        ),
+    }
+}
+
+/// Get the length of a JPEG up to and including its last end of image marker
+/// (0xFFD9), or `None` if it does not start with the start of image marker
+/// (0xFFD8) or has no end of image marker
+pub(crate) fn jpeg_length(image: &[u8]) -> Option<usize> {
+    if !image.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    image.windows(2).rposition(|bytes| bytes == [0xFF, 0xD9]).map(|position| position + 2)
+}
+
+/// Check a FIFO length read after a completed capture.  A disconnected SPI bus
+/// usually reads as all ones, giving a length larger than the FIFO
+pub(crate) fn check_fifo_length<I2CErr, SPIErr>(
+    length: u32
+) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+    if length == 0 || length > MAX_FIFO_SIZE as u32 {
+        return Err(OV2640Error::InvalidFifoLength);
+    }
+    Ok(length)
+}
+
+/// Registers holding the 16-bit exposure, REG04 (AEC[1:0]), AEC (AEC[9:2])
+/// and REG45 (AEC[15:10])
+pub(crate) const EXPOSURE_REGISTERS: [(u8, u8); 3] = [(REG04, 2), (AEC, 8), (REG45, 6)];
+
+/// Mask of the low `bits` bits of a register holding part of a 16-bit value
+pub(crate) fn register16_mask(bits: u8) -> u8 {
+    ((1u16 << bits) - 1) as u8
+}
+
+/// Merge the low `bits` bits of `value` into the current value of a register
+/// holding part of a 16-bit value
+pub(crate) fn register16_part(current: u8, bits: u8, value: u16) -> u8 {
+    let mask = register16_mask(bits);
+    (current & !mask) | (value as u8 & mask)
+}
+
+/// Bits of COM10 set by the sync polarities
+pub(crate) const SYNC_POLARITY_COM10_MASK: u8 =
+    COM10_PCLK_RISING | COM10_HREF_NEGATIVE | COM10_VSYNC_NEGATIVE;
+
+/// Apply the PCLK edge and HREF / VSYNC polarities to the current value of
+/// COM10
+pub(crate) fn sync_polarity_com10(
+    com10: u8, pclk_rising: bool, href_active_high: bool, vsync_active_high: bool
+) -> u8 {
+    let mut com10 = com10 & !SYNC_POLARITY_COM10_MASK;
+    if pclk_rising {
+        com10 |= COM10_PCLK_RISING;
+    }
+    if !href_active_high {
+        com10 |= COM10_HREF_NEGATIVE;
+    }
+    if !vsync_active_high {
+        com10 |= COM10_VSYNC_NEGATIVE;
+    }
+    com10
+}
+
+/// Apply an AGC gain ceiling to the current value of COM9 (bits 7:5)
+pub(crate) fn gain_ceiling_com9(com9: u8, gain_ceiling: GainCeiling) -> u8 {
+    let ceiling = match gain_ceiling {
+        GainCeiling::X2 => 0x00,
+        GainCeiling::X4 => 0x01,
+        GainCeiling::X8 => 0x02,
+        GainCeiling::X16 => 0x03,
+        GainCeiling::X32 => 0x04,
+        GainCeiling::X64 => 0x05,
+        GainCeiling::X128 => 0x06,
+    };
+    (com9 & 0x1F) | (ceiling << 5)
+}
+
+/// Ratio of the uncompressed size of a frame of the configuration to the
+/// captured image length.  JPEG frames are compared against the YUV422 data
+/// (2 bytes per pixel) the encoder compresses
+pub(crate) fn compression_ratio(configuration: &Configuration, image_size: u32) -> f32 {
+    let (width, height) = configuration.frame_dimensions();
+    let bytes_per_pixel = configuration.image_format.bytes_per_pixel().unwrap_or(2);
+    let uncompressed = width as u32 * height as u32 * bytes_per_pixel as u32;
+    uncompressed as f32 / image_size as f32
+}
+
+/// Convert a FIFO length into a buffer length, failing on targets whose `usize`
+/// can not address the whole image (such as 16-bit targets)
+pub(crate) fn buffer_length<I2CErr, SPIErr>(
+    length: u32
+) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+    usize::try_from(length).map_err(|_| OV2640Error::InvalidFifoLength)
+}
+
+/// Assemble the image length from the three FIFO size registers.  Only the
+/// low 7 bits of the high byte are part of the length
+pub(crate) fn fifo_length(len1: u8, len2: u8, len3: u8) -> u32 {
+    u32::from_be_bytes([0x00, len3 & 0x7F, len2, len1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_resolution_matches_the_aspect_ratio_of_the_display() {
+        assert_eq!(display_resolution(320, 240), Some((Resolution::R320x240, (320, 240))));
+        // Square displays get the square resolutions, scaled down if needed
+        assert_eq!(display_resolution(240, 240), Some((Resolution::R128x128, (128, 128))));
+        assert_eq!(display_resolution(128, 160), Some((Resolution::R128x128, (128, 128))));
+        assert_eq!(display_resolution(64, 64), Some((Resolution::R96x96, (64, 64))));
+        // The closest aspect ratio is taken when no resolution matches
+        assert_eq!(display_resolution(480, 320), Some((Resolution::R400x296, (400, 296))));
+        assert_eq!(display_resolution(160, 128), Some((Resolution::R176x144, (156, 128))));
+        assert_eq!(display_resolution(2, 2), None);
+    }
+
+    #[test]
+    fn resolution_tables_output_the_resolution() {
+        for resolution in Resolution::ALL {
+            let configuration = ConfigurationBuilder::new().resolution(resolution).build();
+            let register = |register| expected_dsp_register(&configuration, register).unwrap() as u16;
+            let (zmow, zmoh, zmhh) = (register(ZMOW), register(ZMOH), register(ZMHH));
+
+            // The output size is in units of 4 pixels
+            let width = (((zmhh & 0x03) << 8) | zmow) * 4;
+            let height = (((zmhh & 0x04) << 6) | zmoh) * 4;
+            assert_eq!((width, height), resolution.dimensions(), "{:?}", resolution);
+        }
+    }
+
+    #[test]
+    fn gain_ceilings_map_to_the_com9_ceiling_bits() {
+        let ceilings = [
+            (GainCeiling::X2, 0x00), (GainCeiling::X4, 0x20), (GainCeiling::X8, 0x40),
+            (GainCeiling::X16, 0x60), (GainCeiling::X32, 0x80), (GainCeiling::X64, 0xA0),
+            (GainCeiling::X128, 0xC0),
+        ];
+        for (gain_ceiling, bits) in ceilings {
+            // The low bits of COM9 are kept
+            assert_eq!(gain_ceiling_com9(0xE8, gain_ceiling), bits | 0x08, "{:?}", gain_ceiling);
+        }
+    }
+
+    #[test]
+    fn levels_map_onto_the_sde_register_values() {
+        assert_eq!(brightness_level_registers(-4)[4], [0x7D, 0x00]);
+        assert_eq!(brightness_level_registers(0)[4], [0x7D, 0x20]);
+        assert_eq!(brightness_level_registers(4)[4], [0x7D, 0x40]);
+
+        assert_eq!(contrast_level_registers(-4)[5..7], [[0x7D, 0x18], [0x7D, 0x34]]);
+        assert_eq!(contrast_level_registers(0)[5..7], [[0x7D, 0x20], [0x7D, 0x20]]);
+        assert_eq!(contrast_level_registers(4)[5..7], [[0x7D, 0x28], [0x7D, 0x0C]]);
+
+        assert_eq!(saturation_level_registers(-4)[4..], [[0x7D, 0x28], [0x7D, 0x28]]);
+        assert_eq!(saturation_level_registers(0)[4..], [[0x7D, 0x48], [0x7D, 0x48]]);
+        assert_eq!(saturation_level_registers(4)[4..], [[0x7D, 0x68], [0x7D, 0x68]]);
+
+        // The middle presets are level 0
+        assert_eq!(brightness_registers(Brightness::Brightness2), brightness_level_registers(0));
+        assert_eq!(contrast_registers(Contrast::Contrast2), contrast_level_registers(0));
+    }
+
+    #[test]
+    fn check_level_rejects_levels_out_of_range() {
+        assert!(matches!(check_level::<(), ()>(MIN_LEVEL), Ok(MIN_LEVEL)));
+        assert!(matches!(check_level::<(), ()>(MAX_LEVEL), Ok(MAX_LEVEL)));
+        assert!(matches!(check_level::<(), ()>(MIN_LEVEL - 1), Err(OV2640Error::LevelOutOfRange)));
+        assert!(matches!(check_level::<(), ()>(MAX_LEVEL + 1), Err(OV2640Error::LevelOutOfRange)));
+    }
+
+    #[test]
+    fn r1280x960_uses_its_own_resolution_table() {
+        assert_eq!(resolution_registers(Resolution::R1280x960), &JPEG_1280x960_REGISTERS[..]);
+        assert_ne!(resolution_registers(Resolution::R1280x960), resolution_registers(Resolution::R1280x1024));
+        assert_eq!(lower_resolution(Resolution::R1280x1024), Some(Resolution::R1280x960));
+        assert_eq!(lower_resolution(Resolution::R1280x960), Some(Resolution::R1024x768));
+    }
+
+    #[test]
+    fn square_resolutions_scale_a_centered_square_window() {
+        for resolution in [Resolution::R96x96, Resolution::R128x128] {
+            let configuration = ConfigurationBuilder::new().resolution(resolution).build();
+            let register = |register| expected_dsp_register(&configuration, register).unwrap();
+            // 600x600 of the 800x600 SVGA array, 100 pixels in from the left
+            assert_eq!(register(HSIZE8), register(VSIZE8), "{:?}", resolution);
+            assert_eq!((register(HSIZE), register(VSIZE)), (0x96, 0x96), "{:?}", resolution);
+            assert_eq!((register(XOFFL), register(YOFFL)), (0x64, 0x00), "{:?}", resolution);
+        }
+    }
+}
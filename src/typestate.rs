@@ -0,0 +1,705 @@
+//!
+//! Typestate wrapper around the OV2640 Driver where the presence of the I2C and
+//! SPI peripherals is encoded in the type, so calling a method that needs a
+//! missing peripheral is a compile error instead of `NoI2cPeripheral` or
+//! `NoSpiPeripheral` at runtime
+//!
+
+use core::convert::Infallible;
+
+use embedded_hal::{i2c::{self, I2c, SevenBitAddress}, spi::{self, SpiDevice}, delay::DelayNs};
+
+use crate::config::*;
+use crate::error::OV2640Error;
+use crate::{OV2640, CaptureInProgress, Frame, ModuleInfo, Status};
+
+/// Marker for an OV2640 that is not connected over I2C
+pub enum NoI2c {}
+
+/// Marker for an OV2640 that is not connected over SPI
+pub enum NoSpi {}
+
+/// Peripheral that can never be constructed, standing in for a missing bus
+/// inside the wrapped driver
+pub enum Absent {}
+
+impl i2c::ErrorType for Absent {
+    type Error = Infallible;
+}
+
+impl I2c<SevenBitAddress> for Absent {
+    fn transaction(
+        &mut self, _address: SevenBitAddress, _operations: &mut [i2c::Operation<'_>]
+    ) -> Result<(), Self::Error> {
+        match *self {}
+    }
+}
+
+impl spi::ErrorType for Absent {
+    type Error = Infallible;
+}
+
+impl SpiDevice<u8> for Absent {
+    fn transaction(
+        &mut self, _operations: &mut [spi::Operation<'_, u8>]
+    ) -> Result<(), Self::Error> {
+        match *self {}
+    }
+}
+
+/// Either an I2C peripheral or `NoI2c`
+pub trait I2cSlot {
+    type Bus: I2c<SevenBitAddress>;
+}
+
+impl<I2C: I2c<SevenBitAddress>> I2cSlot for I2C {
+    type Bus = I2C;
+}
+
+impl I2cSlot for NoI2c {
+    type Bus = Absent;
+}
+
+/// Either an SPI peripheral or `NoSpi`
+pub trait SpiSlot {
+    type Bus: SpiDevice<u8>;
+}
+
+impl<SPI: SpiDevice<u8>> SpiSlot for SPI {
+    type Bus = SPI;
+}
+
+impl SpiSlot for NoSpi {
+    type Bus = Absent;
+}
+
+/// OV2640 Driver whose I2C and SPI peripherals are part of its type, with
+/// `NoI2c` or `NoSpi` in place of a bus that is not connected.  Methods needing
+/// a missing bus do not exist, so reading an image without SPI does not compile
+///
+/// ```
+/// use embedded_hal_mock::eh1::i2c::Mock;
+/// use ov2640::{OV2640Typed, NoSpi};
+///
+/// let mut camera: OV2640Typed<Mock, NoSpi> = OV2640Typed::with_i2c(Mock::new(&[]));
+/// camera.configuration();
+/// camera.release_i2c().done();
+/// ```
+///
+/// ```compile_fail
+/// use embedded_hal_mock::eh1::i2c::Mock;
+/// use ov2640::{OV2640Typed, NoSpi};
+///
+/// let mut camera: OV2640Typed<Mock, NoSpi> = OV2640Typed::with_i2c(Mock::new(&[]));
+/// camera.read_image(&mut [0; 64]);
+/// ```
+pub struct OV2640Typed<I2C: I2cSlot, SPI: SpiSlot> {
+    inner: OV2640<I2C::Bus, SPI::Bus>,
+}
+
+impl<I2C: I2cSlot, SPI: SpiSlot> OV2640Typed<I2C, SPI> {
+    /// Get the configuration currently applied by the driver
+    pub fn configuration(&self) -> &Configuration {
+        self.inner.configuration()
+    }
+
+    /// Limit the number of bytes any image read transfers from the FIFO
+    pub fn set_max_read_bytes(&mut self, max_read_bytes: Option<usize>) {
+        self.inner.set_max_read_bytes(max_read_bytes)
+    }
+
+    /// Set the milliseconds waited after a soft reset
+    pub fn set_reset_delay_ms(&mut self, reset_delay_ms: u32) {
+        self.inner.set_reset_delay_ms(reset_delay_ms)
+    }
+}
+
+impl<I2C, SPI, I2CErr, SPIErr> OV2640Typed<I2C, SPI> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr> {
+    /// Initialize a new OV2640 Driver connected over both I2C and SPI
+    pub fn with_i2c_and_spi(i2c: I2C, spi: SPI) -> Self {
+        Self { inner: OV2640::new(Some(i2c), Some(spi)) }
+    }
+
+    /// Check that I2C is correctly connected to the OV2640 Module
+    pub fn i2c_connected(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.i2c_connected()
+    }
+
+    /// Check that SPI is correctly connected to the OV2640 Module
+    pub fn spi_connected(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.spi_connected()
+    }
+
+    /// Probe both interfaces of the module for bring-up diagnostics
+    pub fn probe(&mut self) -> Result<ModuleInfo, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.probe()
+    }
+
+    /// Capture an image like `snapshot`, stepping the resolution down and
+    /// retrying whenever the capture times out
+    pub fn snapshot_with_fallback(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<(usize, Resolution), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.snapshot_with_fallback(buffer, delay, timeout_ms)
+    }
+
+    /// Convert into the driver with runtime checked peripherals
+    pub fn into_inner(self) -> OV2640<I2C, SPI> {
+        self.inner
+    }
+}
+
+impl<I2C, I2CErr> OV2640Typed<I2C, NoSpi> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr> {
+    /// Initialize a new OV2640 Driver connected only over I2C
+    pub fn with_i2c(i2c: I2C) -> Self {
+        Self { inner: OV2640::new(Some(i2c), None::<Absent>) }
+    }
+}
+
+impl<SPI, SPIErr> OV2640Typed<NoI2c, SPI> where
+    SPI: SpiDevice<u8, Error=SPIErr> {
+    /// Initialize a new OV2640 Driver connected only over SPI
+    pub fn with_spi(spi: SPI) -> Self {
+        Self { inner: OV2640::new(None::<Absent>, Some(spi)) }
+    }
+}
+
+impl<I2C, SPI, I2CErr, SPIErr> OV2640Typed<I2C, SPI> where
+    I2C: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiSlot,
+    SPI::Bus: SpiDevice<u8, Error=SPIErr> {
+    /// Initialize the OV2640 Driver with its configuration
+    pub fn init(&mut self, delay: &mut dyn DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.init(delay)
+    }
+
+    /// Initialize the OV2640 Driver, retrying up to `retries` more times when
+    /// an I2C error occurs
+    pub fn init_with_retries(
+        &mut self, delay: &mut dyn DelayNs, retries: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.init_with_retries(delay, retries)
+    }
+
+    /// Set the configuration of the OV2640 Driver
+    pub fn set_configuration(
+        &mut self, configuration: Configuration, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_configuration(configuration, delay)
+    }
+
+    /// Set the image format for the OV2640 Module
+    pub fn set_image_format(
+        &mut self, image_format: ImageFormat, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_image_format(image_format, delay)
+    }
+
+    /// Set the resolution of the OV2640 Module
+    pub fn set_resolution(
+        &mut self, resolution: Resolution
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_resolution(resolution)
+    }
+
+    /// Set the image format and resolution of the OV2640 Module together with
+    /// a single reset
+    pub fn set_format_and_resolution(
+        &mut self, image_format: ImageFormat, resolution: Resolution, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_format_and_resolution(image_format, resolution, delay)
+    }
+
+    /// Configure RGB565 output at the resolution closest to the aspect ratio
+    /// of the display, scaled down to fit it
+    pub fn set_for_display(
+        &mut self, display_width: u16, display_height: u16, delay: &mut dyn DelayNs
+    ) -> Result<(Resolution, (u16, u16)), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_for_display(display_width, display_height, delay)
+    }
+
+    /// Set the light mode of the OV2640 Module
+    pub fn set_light_mode(
+        &mut self, light_mode: LightMode
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_light_mode(light_mode)
+    }
+
+    /// Set the saturation of the OV2640 Module
+    pub fn set_saturation(
+        &mut self, saturation: Saturation
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_saturation(saturation)
+    }
+
+    /// Set the brightness of the OV2640 Module
+    pub fn set_brightness(
+        &mut self, brightness: Brightness
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_brightness(brightness)
+    }
+
+    /// Set the contrast of the OV2640 Module
+    pub fn set_contrast(
+        &mut self, contrast: Contrast
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_contrast(contrast)
+    }
+
+    /// Set the special effect used by the OV2640 Module
+    pub fn set_special_effect(
+        &mut self, special_effect: SpecialEffect
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_special_effect(special_effect)
+    }
+
+    /// Set the JPEG quality of the OV2640 Module
+    pub fn set_jpeg_quality(
+        &mut self, jpeg_quality: JpegQuality
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_jpeg_quality(jpeg_quality)
+    }
+
+    /// Set the horizontal mirror and vertical flip of the OV2640 Module
+    pub fn set_flip(
+        &mut self, flip: Flip
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_flip(flip)
+    }
+
+    /// Set the strobe (flash light) output of the OV2640 Module
+    pub fn set_strobe(
+        &mut self, strobe: StrobeMode
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_strobe(strobe)
+    }
+
+    /// Set the banding filter of the OV2640 Module
+    pub fn set_banding_filter(
+        &mut self, banding_filter: BandingFilter
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_banding_filter(banding_filter)
+    }
+
+    /// Turn the automatic mains frequency detection of the banding filter on
+    /// or off
+    pub fn set_auto_banding(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_auto_banding(on)
+    }
+
+    /// Crop the output to a window of the sensor array
+    pub fn set_window(
+        &mut self, x: u16, y: u16, width: u16, height: u16
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_window(x, y, width, height)
+    }
+
+    /// Set the sharpness (edge enhancement) of the OV2640 Module
+    pub fn set_sharpness(
+        &mut self, sharpness: Sharpness
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_sharpness(sharpness)
+    }
+
+    /// Set the white balance of the OV2640 Module
+    pub fn set_white_balance(
+        &mut self, white_balance: WhiteBalance
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_white_balance(white_balance)
+    }
+
+    /// Soft reset the sensor without reconfiguring it, `init` must be called again
+    pub fn soft_reset(
+        &mut self, delay: &mut dyn DelayNs
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.soft_reset(delay)
+    }
+
+    /// Set a custom gamma curve of the OV2640 Module
+    pub fn set_gamma_curve(
+        &mut self, points: &[u8; GAMMA_CURVE_POINTS]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_gamma_curve(points)
+    }
+
+    /// Replace the image with the internal color bar test pattern
+    pub fn set_test_pattern(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_test_pattern(on)
+    }
+
+    /// Turn night mode on or off, trading frame rate for brighter low light frames
+    pub fn set_night_mode(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_night_mode(on)
+    }
+
+    /// Set the exposure of the OV2640 Module
+    pub fn set_exposure(
+        &mut self, exposure: Exposure
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_exposure(exposure)
+    }
+
+    /// Read the current exposure of the OV2640 Module
+    pub fn exposure(&mut self) -> Result<u16, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.exposure()
+    }
+
+    /// Set the ceiling of the automatic gain of the OV2640 Module
+    pub fn set_gain_ceiling(
+        &mut self, gain_ceiling: GainCeiling
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_gain_ceiling(gain_ceiling)
+    }
+
+    /// Write a register table, such as a vendor tuning list, to the OV2640 Module
+    pub fn apply_register_table(
+        &mut self, table: &[[u8; 2]]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.apply_register_table(table)
+    }
+
+    /// Read back the image format and output size registers, returning false on a mismatch
+    pub fn verify_configuration(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.verify_configuration()
+    }
+
+    /// Set the clock divider (CLKRC) of the OV2640 Module
+    pub fn set_clock_divider(
+        &mut self, clock_divider: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_clock_divider(clock_divider)
+    }
+
+    /// Set the raw red, green and blue white balance gains
+    pub fn set_white_balance_raw(
+        &mut self, regs: [u8; 3]
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_white_balance_raw(regs)
+    }
+
+    /// Set the saturation as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Saturation2` and each `Saturation` step is two levels
+    pub fn set_saturation_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_saturation_level(level)
+    }
+
+    /// Set the brightness as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Brightness2` and each `Brightness` step is two levels
+    pub fn set_brightness_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_brightness_level(level)
+    }
+
+    /// Set the contrast as a level in `MIN_LEVEL..=MAX_LEVEL`, where 0 matches
+    /// `Contrast2` and each `Contrast` step is two levels
+    pub fn set_contrast_level(
+        &mut self, level: i8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_contrast_level(level)
+    }
+
+    /// Read the raw (high, low) product ID of the sensor
+    pub fn product_id(&mut self) -> Result<(u8, u8), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.product_id()
+    }
+
+    /// Put the sensor into (or wake it from) soft standby through the COM2
+    /// standby bit, call `init` again after waking
+    pub fn set_power_down(
+        &mut self, down: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_power_down(down)
+    }
+
+    /// Swap the byte order of each RGB565 or YUV422 pixel in the DSP
+    pub fn set_byte_swap(
+        &mut self, swap: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_byte_swap(swap)
+    }
+
+    /// Turn the lens shading correction (LENC) of the DSP on or off
+    pub fn set_lens_correction(
+        &mut self, on: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_lens_correction(on)
+    }
+
+    /// Bypass the DSP to read the raw Bayer output of the sensor, the image
+    /// format and settings do not apply while bypassed
+    pub fn set_dsp_bypass(
+        &mut self, bypass: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_dsp_bypass(bypass)
+    }
+
+    /// Set the polarities of the parallel (DVP) output through COM10
+    pub fn set_sync_polarity(
+        &mut self, pclk_rising: bool, href_active_high: bool, vsync_active_high: bool
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_sync_polarity(pclk_rising, href_active_high, vsync_active_high)
+    }
+
+    /// Read every readable register of the given bank into `out`, returning
+    /// the number of registers read
+    pub fn dump_registers(
+        &mut self, bank: u8, out: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.dump_registers(bank, out)
+    }
+
+    /// Tint the image with fixed U and V chroma values
+    pub fn set_special_effect_raw(
+        &mut self, u: u8, v: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.set_special_effect_raw(u, v)
+    }
+
+    /// Read the average luminance of the last frame as measured by the auto
+    /// exposure engine (YAVG)
+    pub fn average_luminance(&mut self) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.average_luminance()
+    }
+
+    /// Check that the DSP output size fits within the DSP input window
+    pub fn validate_window_registers(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.validate_window_registers()
+    }
+
+    /// Read a register from the given bank
+    pub fn read_sensor_register(
+        &mut self, bank: u8, register: u8
+    ) -> Result<u8, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_sensor_register(bank, register)
+    }
+
+    /// Write a register in the given bank
+    pub fn write_sensor_register(
+        &mut self, bank: u8, register: u8, value: u8
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.write_sensor_register(bank, register, value)
+    }
+
+    /// Soft reset the sensor
+    pub fn reset(&mut self, delay: &mut dyn DelayNs) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.reset(delay)
+    }
+
+    /// Release the I2C Peripheral from the device
+    pub fn release_i2c(self) -> I2C {
+        let mut inner = self.inner;
+        inner.take_i2c().expect("typestate guarantees an I2C peripheral")
+    }
+}
+
+impl<I2C, SPI, I2CErr, SPIErr> OV2640Typed<I2C, SPI> where
+    I2C: I2cSlot,
+    I2C::Bus: I2c<SevenBitAddress, Error=I2CErr>,
+    SPI: SpiDevice<u8, Error=SPIErr> {
+    /// Power down the sensor
+    pub fn power_down(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.power_down()
+    }
+
+    /// Power up the sensor
+    pub fn power_up(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.power_up()
+    }
+
+    /// Flush the OV2640's FIFO
+    pub fn flush_fifo(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.flush_fifo()
+    }
+
+    /// Abort a capture in progress, clearing the FIFO
+    pub fn abort_capture(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.abort_capture()
+    }
+
+    /// Start capturing into the FIFO, returning a handle that can be polled
+    /// for the capture to complete
+    pub fn start_capture(
+        &mut self
+    ) -> Result<CaptureInProgress<'_, I2C::Bus, SPI>, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.start_capture()
+    }
+
+    /// Check whether the capture is complete
+    pub fn is_capture_done(&mut self) -> Result<bool, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.is_capture_done()
+    }
+
+    /// Read and decode the ArduChip trigger register
+    pub fn status(&mut self) -> Result<Status, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.status()
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// until `timeout_ms` milliseconds have passed
+    pub fn capture(
+        &mut self, delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.capture(delay, timeout_ms)
+    }
+
+    /// Clear the FIFO and capture an image, polling for the capture to complete
+    /// every `poll_interval_ms` milliseconds until `timeout_ms` milliseconds
+    /// have passed
+    pub fn capture_with_poll_interval(
+        &mut self, delay: &mut dyn DelayNs, timeout_ms: u32, poll_interval_ms: u32
+    ) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.capture_with_poll_interval(delay, timeout_ms, poll_interval_ms)
+    }
+
+    /// Get the length of the image in the FIFO
+    pub fn image_size(&mut self) -> Result<u32, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.image_size()
+    }
+
+    /// Get the approximate compression ratio of the captured image
+    pub fn last_compression_ratio(&mut self) -> Result<f32, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.last_compression_ratio()
+    }
+
+    /// Read the captured image into the provided buffer, returning the image
+    /// length in bytes
+    pub fn read_image(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image(buffer)
+    }
+
+    /// Stream the captured image through `chunk`, handing each filled chunk to
+    /// `sink`
+    pub fn read_image_chunked(
+        &mut self, chunk: &mut [u8], sink: impl FnMut(&[u8])
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image_chunked(chunk, sink)
+    }
+
+    /// Read the captured JPEG, trimming the padding after the end of image
+    /// marker
+    pub fn read_jpeg_trimmed(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_jpeg_trimmed(buffer)
+    }
+
+    /// Read the captured image into a buffer aligned to `alignment` bytes
+    pub fn read_image_aligned(
+        &mut self, buffer: &mut [u8], alignment: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image_aligned(buffer, alignment)
+    }
+
+    /// Read the captured image into the provided buffer in `block`-sized SPI
+    /// transfers, returning the image length in bytes
+    pub fn read_image_blocks(
+        &mut self, buffer: &mut [u8], block: usize
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image_blocks(buffer, block)
+    }
+
+    /// Read the captured image, recording the throughput of the read
+    pub fn read_image_timed(
+        &mut self, buffer: &mut [u8], now_us: impl FnMut() -> u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image_timed(buffer, now_us)
+    }
+
+    /// Start a capture and return without waiting for it to complete
+    pub fn capture_nonblocking_start(&mut self) -> Result<(), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.capture_nonblocking_start()
+    }
+
+    /// Read the image of a capture started with `capture_nonblocking_start`
+    pub fn collect_after_capture(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.collect_after_capture(buffer)
+    }
+
+    /// Capture an image and read it into the provided buffer
+    pub fn snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.snapshot(buffer, delay, timeout_ms)
+    }
+
+    /// Flush the FIFO, capture and read the next frame of a stream into the
+    /// provided buffer, returning the image length in bytes
+    pub fn stream_next(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.stream_next(buffer, delay, timeout_ms)
+    }
+
+    /// Capture an image and read it into the provided buffer, returning the
+    /// image length in bytes and the elapsed milliseconds measured by `now_ms`
+    pub fn timed_snapshot(
+        &mut self, buffer: &mut [u8], delay: &mut dyn DelayNs, timeout_ms: u32,
+        now_ms: impl FnMut() -> u32
+    ) -> Result<(usize, u32), OV2640Error<I2CErr, SPIErr>> {
+        self.inner.timed_snapshot(buffer, delay, timeout_ms, now_ms)
+    }
+
+    /// Read the captured image without the leading dummy byte of some ArduCam boards
+    pub fn read_image_exact(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_image_exact(buffer)
+    }
+
+    /// Read the captured JPEG into the provided buffer, failing with
+    /// `CorruptJpeg` if it is missing the start or end of image marker
+    pub fn read_jpeg(
+        &mut self, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_jpeg(buffer)
+    }
+
+    /// Read `rows` scanlines starting at `start_row` of a captured uncompressed
+    /// image into the provided buffer, returning the band length in bytes
+    pub fn read_scanlines(
+        &mut self, start_row: u16, rows: u16, buffer: &mut [u8]
+    ) -> Result<usize, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_scanlines(start_row, rows, buffer)
+    }
+
+    /// Read the captured image into the provided buffer, returning it as a
+    /// `Frame` with the format and dimensions of the current configuration
+    pub fn read_frame<'a>(
+        &mut self, buffer: &'a mut [u8]
+    ) -> Result<Frame<'a>, OV2640Error<I2CErr, SPIErr>> {
+        self.inner.read_frame(buffer)
+    }
+
+    /// Bytes per second of the last timed image read
+    pub fn last_read_throughput(&self) -> Option<u32> {
+        self.inner.last_read_throughput()
+    }
+
+    /// Get the largest image in bytes the current configuration can produce,
+    /// for sizing the image buffer
+    pub fn max_image_size(&self) -> usize {
+        self.inner.max_image_size()
+    }
+
+    /// Release the SPI Peripheral from the device
+    pub fn release_spi(self) -> SPI {
+        let mut inner = self.inner;
+        inner.take_spi().expect("typestate guarantees an SPI peripheral")
+    }
+}